@@ -21,3 +21,7 @@ ptime -m cargo fmt -- --check
 
 banner test
 ptime -m cargo test --release --no-fail-fast --verbose
+
+banner no_std
+rustup target add thumbv7em-none-eabi
+ptime -m cargo build --no-default-features --target thumbv7em-none-eabi
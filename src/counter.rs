@@ -0,0 +1,106 @@
+//! Best-effort classification of kstats as counters or gauges.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::unit::DEFAULT_SUFFIXES;
+use crate::Unit;
+use std::sync::OnceLock;
+
+/// Heuristics for deciding whether kstat values are monotonic counters.
+///
+/// Kstats do not record whether a value is a counter, which only increases, or a gauge, which
+/// may move in either direction. This guesses from the class of the kstat, and from the names
+/// of its statistics: a statistic is a counter only if its kstat is in one of the counter
+/// classes and its name has one of the counter suffixes. Even counter classes hold some gauges,
+/// such as `link_state` in `net` kstats, which the suffixes exclude. It is inherently
+/// best-effort, and the lists may be replaced or extended to suit a particular system.
+///
+/// The default suffixes are those which [`UnitRules`](crate::UnitRules) infers to count bytes or items.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CounterHeuristic {
+    /// Classes of kstats which hold counters.
+    pub classes: Vec<String>,
+    /// Suffixes of the names of statistics which are counters, in a counter class.
+    pub suffixes: Vec<String>,
+}
+
+impl Default for CounterHeuristic {
+    fn default() -> Self {
+        let classes = ["controller", "disk", "intr", "net", "partition", "tape"];
+        CounterHeuristic {
+            classes: classes.iter().map(|s| s.to_string()).collect(),
+            suffixes: DEFAULT_SUFFIXES
+                .iter()
+                .filter(|(_, unit)| matches!(unit, Unit::Bytes | Unit::Count))
+                .map(|(s, _)| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl CounterHeuristic {
+    // The default heuristic, built once.
+    pub(crate) fn default_ref() -> &'static CounterHeuristic {
+        static DEFAULT: OnceLock<CounterHeuristic> = OnceLock::new();
+        DEFAULT.get_or_init(CounterHeuristic::default)
+    }
+
+    /// Return `true` if kstats in `class` hold counters.
+    pub fn is_counter_class(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
+
+    /// Return `true` if the statistic `name` has a counter suffix.
+    pub fn is_counter_name(&self, name: &str) -> bool {
+        self.suffixes.iter().any(|s| name.ends_with(s.as_str()))
+    }
+
+    /// Return `true` if the statistic `name` of a kstat in `class` is treated as a counter.
+    ///
+    /// Both the class and the name must match.
+    pub fn is_counter(&self, class: &str, name: &str) -> bool {
+        self.is_counter_class(class) && self.is_counter_name(name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_heuristic() {
+        let h = CounterHeuristic::default();
+        assert!(h.is_counter_class("disk"));
+        assert!(h.is_counter_class("net"));
+        assert!(!h.is_counter_class("misc"));
+        assert!(h.is_counter("net", "rbytes64"));
+        assert!(h.is_counter("net", "ierrors"));
+        assert!(h.is_counter("disk", "reads_total"));
+        assert!(!h.is_counter("net", "link_state"));
+        assert!(!h.is_counter("misc", "rbytes64"));
+        assert!(!h.is_counter("misc", "clock_MHz"));
+        assert!(!h.is_counter_name("current_clock_Hz"));
+    }
+
+    #[test]
+    fn heuristic_is_overridable() {
+        let mut h = CounterHeuristic::default();
+        h.classes.push(String::from("misc"));
+        assert!(h.is_counter("misc", "rbytes64"));
+        assert!(!h.is_counter("hat", "rbytes64"));
+        h.suffixes.clear();
+        assert!(!h.is_counter("net", "rbytes64"));
+    }
+}
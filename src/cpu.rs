@@ -0,0 +1,123 @@
+//! Helpers for extracting CPU topology from `cpu_info` kstats.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Data;
+use crate::Error;
+use crate::Instance;
+use crate::Kstat;
+use crate::NamedData;
+
+/// The topology and state of a single CPU, from its `cpu_info` kstat.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CpuInfo {
+    /// The instance of the kstat, which is the CPU ID.
+    pub instance: i32,
+    /// The ID of the physical chip, or socket, containing the CPU.
+    pub chip_id: i64,
+    /// The ID of the core containing the CPU.
+    pub core_id: i64,
+    /// The ID of the processor group containing the CPU.
+    pub pg_id: i64,
+    /// The current clock speed of the CPU, in MHz.
+    pub clock_mhz: i64,
+    /// The state of the CPU, such as `on-line`.
+    pub state: String,
+}
+
+/// Read the topology of every CPU from the `cpu_info` kstats, sorted by instance.
+///
+/// CPUs whose kstat lacks one of the fields of [`CpuInfo`] are skipped, and logged if the `log`
+/// feature is enabled, as are kstats which are removed from the chain while being read. Any other
+/// failure to read a kstat is returned.
+pub fn cpu_topology(ctl: &Ctl) -> Result<Vec<CpuInfo>, Error> {
+    let mut cpus = ctl.decode_each(ctl.filter(Some("cpu_info"), Instance::Any, None), cpu)?;
+    cpus.sort_by_key(|cpu| cpu.instance);
+    Ok(cpus)
+}
+
+fn cpu(kstat: &Kstat<'_>, data: &Data<'_>) -> Result<CpuInfo, Error> {
+    Ok(CpuInfo {
+        instance: kstat.ks_instance,
+        chip_id: data.named_i64("chip_id")?,
+        core_id: data.named_i64("core_id")?,
+        pg_id: data.named_i64("pg_id")?,
+        clock_mhz: data.named_i64("clock_MHz")?,
+        state: str_field(data, "state")?,
+    })
+}
+
+fn str_field(data: &Data<'_>, name: &str) -> Result<String, Error> {
+    match data.named_value(name) {
+        Some(NamedData::String(s)) => Ok(s.to_string()),
+        Some(value @ NamedData::Char(_)) => Ok(value.as_char_str().unwrap()?.to_string()),
+        _ => Err(Error::NotFound),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // This reads the real kstat chain, which the stub backend hides.
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn cpu_topology_finds_cpus() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let cpus = cpu_topology(&ctl).expect("Failed to read CPU topology");
+        assert!(!cpus.is_empty());
+        assert!(cpus.windows(2).all(|w| w[0].instance < w[1].instance));
+        assert!(cpus.iter().all(|cpu| !cpu.state.is_empty()));
+    }
+
+    #[cfg(feature = "stubs")]
+    #[test]
+    fn cpu_topology_skips_incomplete_cpus() {
+        use crate::FakeKstat;
+        use crate::Type;
+
+        let cpu = |instance, state| {
+            FakeKstat::new(
+                "cpu_info",
+                instance,
+                &format!("cpu_info{}", instance),
+                Type::Named,
+            )
+            .class("misc")
+            .named("chip_id", NamedData::Int64(0))
+            .named("core_id", NamedData::Int64(i64::from(instance)))
+            .named("pg_id", NamedData::Int64(1))
+            .named("clock_MHz", NamedData::Int64(2000))
+            .named("state", NamedData::String(state))
+        };
+        let _chain = crate::stub::set_chain(vec![
+            cpu(1, "on-line"),
+            FakeKstat::new("cpu_info", 2, "cpu_info2", Type::Named)
+                .class("misc")
+                .named("chip_id", NamedData::Int64(0)),
+            cpu(0, "off-line"),
+        ]);
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let cpus = cpu_topology(&ctl).expect("Failed to read CPU topology");
+        assert_eq!(
+            cpus.iter().map(|cpu| cpu.instance).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(cpus[0].state, "off-line");
+        assert_eq!(cpus[1].core_id, 1);
+    }
+}
@@ -0,0 +1,93 @@
+//! Export named kstats as comma-separated values.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::KstatId;
+use crate::Named;
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Render named statistics of a kstat as comma-separated values.
+///
+/// Each statistic becomes one line of the form `module,instance,name,stat,value`, with the value
+/// formatted as by its `Display` implementation. No header line is included. Fields containing
+/// a comma, a double quote, or a line break are quoted, with any double quotes doubled.
+pub fn named_to_csv(id: &KstatId, stats: &[Named<'_>]) -> String {
+    let mut out = String::new();
+    let module = quote(&id.module);
+    let name = quote(&id.name);
+    for stat in stats.iter() {
+        let value = stat.value.to_string();
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            module,
+            id.instance,
+            name,
+            quote(stat.name),
+            quote(&value)
+        )
+        .unwrap();
+    }
+    out
+}
+
+// Quote a field, if it contains any characters which are special in CSV.
+fn quote(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::NamedData;
+
+    #[test]
+    fn one_row_per_stat() {
+        let id = KstatId::new("cpu_info", 0, "cpu_info0");
+        let stats = [
+            Named {
+                name: "clock_MHz",
+                value: NamedData::Int64(2000),
+            },
+            Named {
+                name: "state",
+                value: NamedData::String("on-line"),
+            },
+            Named {
+                name: "brand",
+                value: NamedData::String("Fast, \"new\" CPU"),
+            },
+        ];
+        let csv = named_to_csv(&id, &stats);
+        assert_eq!(csv.lines().count(), stats.len());
+        assert_eq!(
+            csv,
+            "cpu_info,0,cpu_info0,clock_MHz,2000\n\
+             cpu_info,0,cpu_info0,state,on-line\n\
+             cpu_info,0,cpu_info0,brand,\"Fast, \"\"new\"\" CPU\"\n"
+        );
+    }
+
+    #[test]
+    fn empty_stats() {
+        let id = KstatId::new("m", 0, "n");
+        assert_eq!(named_to_csv(&id, &[]), "");
+    }
+}
@@ -0,0 +1,171 @@
+//! Computing differences between snapshots of named kstats, and of the kstat chain.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::KstatId;
+use crate::OwnedNamed;
+use crate::OwnedNamedData;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+/// The change in a single named statistic between two snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NamedDelta {
+    /// The statistic is present in both snapshots.
+    ///
+    /// For integer values of the same type, `value` is the difference between the new and old
+    /// values, wrapping on overflow as kernel counters do. For all other values, it is the new
+    /// value.
+    Changed { name: String, value: OwnedNamedData },
+    /// The statistic is present only in the new snapshot.
+    Added(OwnedNamed),
+    /// The statistic is present only in the old snapshot.
+    Removed(OwnedNamed),
+}
+
+/// The kstats added to and removed from the chain by an update, from [`Ctl::update_diff`].
+///
+/// [`Ctl::update_diff`]: crate::Ctl::update_diff
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChainDiff {
+    /// The kstats present only after the update, sorted.
+    pub added: Vec<KstatId>,
+    /// The kstats present only before the update, sorted.
+    pub removed: Vec<KstatId>,
+}
+
+impl ChainDiff {
+    /// Return `true` if no kstats were added or removed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+// Compute the kstats added and removed between two sets of kstats.
+pub(crate) fn diff_chain(old: &BTreeSet<KstatId>, new: &BTreeSet<KstatId>) -> ChainDiff {
+    ChainDiff {
+        added: new.difference(old).cloned().collect(),
+        removed: old.difference(new).cloned().collect(),
+    }
+}
+
+/// Compute the differences between two snapshots of a named kstat.
+///
+/// Statistics are matched by name. The result contains a [`NamedDelta::Changed`] or
+/// [`NamedDelta::Added`] entry for each statistic in `new`, in order, followed by a
+/// [`NamedDelta::Removed`] entry for each statistic only in `old`.
+pub fn diff_named(old: &[OwnedNamed], new: &[OwnedNamed]) -> Vec<NamedDelta> {
+    let old_by_name = old
+        .iter()
+        .map(|n| (n.name.as_str(), n))
+        .collect::<BTreeMap<_, _>>();
+    let new_by_name = new
+        .iter()
+        .map(|n| (n.name.as_str(), n))
+        .collect::<BTreeMap<_, _>>();
+    let changed = new.iter().map(|n| match old_by_name.get(n.name.as_str()) {
+        Some(o) => NamedDelta::Changed {
+            name: n.name.clone(),
+            value: delta(&o.value, &n.value),
+        },
+        None => NamedDelta::Added(n.clone()),
+    });
+    let removed = old
+        .iter()
+        .filter(|o| !new_by_name.contains_key(o.name.as_str()))
+        .map(|o| NamedDelta::Removed(o.clone()));
+    changed.chain(removed).collect()
+}
+
+// Compute the difference between two values of a statistic.
+fn delta(old: &OwnedNamedData, new: &OwnedNamedData) -> OwnedNamedData {
+    match (old, new) {
+        (OwnedNamedData::Int32(o), OwnedNamedData::Int32(n)) => {
+            OwnedNamedData::Int32(n.wrapping_sub(*o))
+        }
+        (OwnedNamedData::UInt32(o), OwnedNamedData::UInt32(n)) => {
+            OwnedNamedData::UInt32(n.wrapping_sub(*o))
+        }
+        (OwnedNamedData::Int64(o), OwnedNamedData::Int64(n)) => {
+            OwnedNamedData::Int64(n.wrapping_sub(*o))
+        }
+        (OwnedNamedData::UInt64(o), OwnedNamedData::UInt64(n)) => {
+            OwnedNamedData::UInt64(n.wrapping_sub(*o))
+        }
+        (_, new) => new.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn named(name: &str, value: OwnedNamedData) -> OwnedNamed {
+        OwnedNamed {
+            name: name.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn diff_named_snapshots() {
+        let old = vec![
+            named("packets", OwnedNamedData::UInt64(10)),
+            named("state", OwnedNamedData::String("down".to_string())),
+            named("gone", OwnedNamedData::UInt32(1)),
+            named("wrapped", OwnedNamedData::UInt32(u32::MAX)),
+        ];
+        let new = vec![
+            named("packets", OwnedNamedData::UInt64(25)),
+            named("state", OwnedNamedData::String("up".to_string())),
+            named("wrapped", OwnedNamedData::UInt32(1)),
+            named("fresh", OwnedNamedData::Int64(-3)),
+        ];
+        assert_eq!(
+            diff_named(&old, &new),
+            vec![
+                NamedDelta::Changed {
+                    name: "packets".to_string(),
+                    value: OwnedNamedData::UInt64(15),
+                },
+                NamedDelta::Changed {
+                    name: "state".to_string(),
+                    value: OwnedNamedData::String("up".to_string()),
+                },
+                NamedDelta::Changed {
+                    name: "wrapped".to_string(),
+                    value: OwnedNamedData::UInt32(2),
+                },
+                NamedDelta::Added(named("fresh", OwnedNamedData::Int64(-3))),
+                NamedDelta::Removed(named("gone", OwnedNamedData::UInt32(1))),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_chain_sets() {
+        let old = [KstatId::new("sd", 0, "sd0"), KstatId::new("sd", 1, "sd1")]
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        let new = [KstatId::new("sd", 1, "sd1"), KstatId::new("sd", 2, "sd2")]
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        let diff = diff_chain(&old, &new);
+        assert_eq!(diff.added, [KstatId::new("sd", 2, "sd2")]);
+        assert_eq!(diff.removed, [KstatId::new("sd", 0, "sd0")]);
+        assert!(!diff.is_empty());
+        assert!(diff_chain(&old, &old).is_empty());
+    }
+}
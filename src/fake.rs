@@ -0,0 +1,486 @@
+//! A builder for synthetic kstats, for testing code which uses this crate.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::sys;
+use crate::Data;
+use crate::Error;
+use crate::Intr;
+use crate::Io;
+use crate::Kstat;
+use crate::KstatFlags;
+use crate::NamedData;
+use crate::Timer;
+use crate::Type;
+use crate::NAMED_CHAR_LEN;
+use crate::STRLEN;
+use libc::c_char;
+use std::mem::size_of;
+
+/// A synthetic kstat, built in memory rather than read from the kernel.
+///
+/// This lays out a `kstat_t` and its data buffer exactly as `libkstat` would, so that the
+/// [`Kstat`] and [`Data`] returned from it behave as if they had been read from the kernel. It
+/// is intended for testing code which uses this crate on systems without kstats.
+///
+/// The methods which add data panic if the data does not match the type of the kstat, or if a
+/// string is too long for its field.
+///
+/// ```
+/// use kstat_rs::{Data, FakeKstat, NamedData, Type};
+///
+/// let fake = FakeKstat::new("unix", 0, "system_misc", Type::Named)
+///     .class("misc")
+///     .named("ncpus", NamedData::UInt32(4));
+/// assert_eq!(fake.kstat().ks_module, "unix");
+/// let Data::Named(named) = fake.data().unwrap() else {
+///     panic!("expected named data");
+/// };
+/// assert_eq!(named[0].name, "ncpus");
+/// assert_eq!(named[0].value, NamedData::UInt32(4));
+/// ```
+#[derive(Debug)]
+pub struct FakeKstat {
+    pub(crate) ks: sys::kstat_t,
+    // The data buffer, as `u64`s so that it is suitably aligned for any of the data types.
+    data: Vec<u64>,
+    // The storage for the values of any `String` named data.
+    strings: Vec<Box<[u8]>>,
+}
+
+impl FakeKstat {
+    /// Create a new, empty kstat with the given module, instance, name, and type.
+    pub fn new(module: &str, instance: i32, name: &str, ty: Type) -> Self {
+        let mut ks: sys::kstat_t = unsafe { std::mem::zeroed() };
+        ks.ks_module = to_array(module);
+        ks.ks_instance = instance;
+        ks.ks_name = to_array(name);
+        ks.ks_type = u8::from(ty);
+        Self {
+            ks,
+            data: Vec::new(),
+            strings: Vec::new(),
+        }
+    }
+
+    /// Set the class of the kstat.
+    pub fn class(mut self, class: &str) -> Self {
+        self.ks.ks_class = to_array(class);
+        self
+    }
+
+    /// Set the kstat ID of the kstat.
+    pub fn kid(mut self, kid: i32) -> Self {
+        self.ks.ks_kid = kid;
+        self
+    }
+
+    /// Set the creation time of the kstat.
+    pub fn crtime(mut self, crtime: i64) -> Self {
+        self.ks.ks_crtime = crtime;
+        self
+    }
+
+    /// Set the time the kstat was last read.
+    pub fn snaptime(mut self, snaptime: i64) -> Self {
+        self.ks.ks_snaptime = snaptime;
+        self
+    }
+
+    /// Set the flags of the kstat.
+    pub fn flags(mut self, flags: KstatFlags) -> Self {
+        self.ks.ks_flags = flags.bits() as c_char;
+        self
+    }
+
+    /// Append a raw data element.
+    ///
+    /// All the elements of a raw kstat must be the same size.
+    pub fn raw(mut self, element: &[u8]) -> Self {
+        self.check_type(Type::Raw);
+        if self.ks.ks_ndata > 0 {
+            let size = self.ks.ks_data_size / self.ks.ks_ndata as usize;
+            assert_eq!(
+                element.len(),
+                size,
+                "raw elements must all be the same size"
+            );
+        }
+        self.push_bytes(element);
+        self
+    }
+
+    /// Append a name-value pair.
+    ///
+    /// A `Char` value must be at most [`NAMED_CHAR_LEN`] bytes, and is padded with NULs to that
+    /// length.
+    pub fn named(mut self, name: &str, value: NamedData<'_>) -> Self {
+        self.check_type(Type::Named);
+        assert_eq!(
+            self.ks.ks_data_size,
+            self.ks.ks_ndata as usize * size_of::<sys::kstat_named_t>(),
+            "name-value pairs cannot be added after inlining strings"
+        );
+        let mut named: sys::kstat_named_t = unsafe { std::mem::zeroed() };
+        named.name = to_array(name);
+        named.data_type = u8::from(value.data_type());
+        match value {
+            NamedData::Char(bytes) => {
+                assert!(
+                    bytes.len() <= NAMED_CHAR_LEN,
+                    "a named char value is at most {} bytes",
+                    NAMED_CHAR_LEN
+                );
+                let mut charc = [0; NAMED_CHAR_LEN];
+                charc[..bytes.len()].copy_from_slice(bytes);
+                named.value.charc = charc;
+            }
+            NamedData::Int32(x) => named.value.i32 = x,
+            NamedData::UInt32(x) => named.value.ui32 = x,
+            NamedData::Int64(x) => named.value.i64 = x,
+            NamedData::UInt64(x) => named.value.ui64 = x,
+            NamedData::Float(x) => named.value.f = x,
+            NamedData::Double(x) => named.value.d = x,
+            NamedData::String(s) => {
+                let mut bytes = Vec::with_capacity(s.len() + 1);
+                bytes.extend_from_slice(s.as_bytes());
+                bytes.push(0);
+                let bytes = bytes.into_boxed_slice();
+                named.value.str = sys::NamedStr {
+                    addr: bytes.as_ptr() as *const c_char,
+                    len: bytes.len() as _,
+                };
+                self.strings.push(bytes);
+            }
+        }
+        self.push_bytes(as_bytes(&named));
+        self
+    }
+
+    /// Move the values of any `String` name-value pairs into the data buffer, after the array
+    /// of pairs.
+    ///
+    /// This is how the kernel lays out named kstats with string data, so the size of the data
+    /// buffer is then not a multiple of the size of a pair. No pairs may be added afterwards.
+    pub fn inline_strings(mut self) -> Self {
+        self.check_type(Type::Named);
+        let mut offsets = Vec::new();
+        for i in 0..self.ks.ks_ndata as usize {
+            let named = unsafe { &*(self.ks.ks_data as *const sys::kstat_named_t).add(i) };
+            if named.data_type == sys::KSTAT_DATA_STRING {
+                let s = unsafe { named.value.str };
+                let bytes =
+                    unsafe { std::slice::from_raw_parts(s.addr as *const u8, s.len as _) }.to_vec();
+                offsets.push((i, self.extend(&bytes)));
+            }
+        }
+        // The buffer may have moved while extending it, so point at the strings only once they
+        // are all in place.
+        let base = self.data.as_mut_ptr() as *mut u8;
+        for (i, offset) in offsets {
+            let named = unsafe { &mut *(base as *mut sys::kstat_named_t).add(i) };
+            named.value.str.addr = unsafe { base.add(offset) } as *const c_char;
+        }
+        self.strings.clear();
+        self
+    }
+
+    /// Set the interrupt statistics.
+    pub fn intr(mut self, intr: Intr) -> Self {
+        self.check_type(Type::Intr);
+        assert_eq!(
+            self.ks.ks_ndata, 0,
+            "an interrupt kstat has a single element"
+        );
+        let intr = sys::kstat_intr_t {
+            intr_hard: intr.hard,
+            intr_soft: intr.soft,
+            intr_watchdog: intr.watchdog,
+            intr_spurious: intr.spurious,
+            intr_multisvc: intr.multisvc,
+        };
+        self.push_bytes(as_bytes(&intr));
+        self
+    }
+
+    /// Set the I/O statistics.
+    pub fn io(mut self, io: Io) -> Self {
+        self.check_type(Type::Io);
+        assert_eq!(self.ks.ks_ndata, 0, "an I/O kstat has a single element");
+        let io = sys::kstat_io_t {
+            nread: io.nread,
+            nwritten: io.nwritten,
+            reads: io.reads,
+            writes: io.writes,
+            wtime: io.wtime,
+            wlentime: io.wlentime,
+            wlastupdate: io.wlastupdate,
+            rtime: io.rtime,
+            rlentime: io.rlentime,
+            rlastupdate: io.rlastupdate,
+            wcnt: io.wcnt,
+            rcnt: io.rcnt,
+        };
+        self.push_bytes(as_bytes(&io));
+        self
+    }
+
+    /// Append a timer.
+    pub fn timer(mut self, timer: Timer<'_>) -> Self {
+        self.check_type(Type::Timer);
+        let mut t: sys::kstat_timer_t = unsafe { std::mem::zeroed() };
+        t.name = to_array(timer.name);
+        t.num_events = timer.num_events as _;
+        t.elapsed_time = timer.elapsed_time;
+        t.min_time = timer.min_time;
+        t.max_time = timer.max_time;
+        t.start_time = timer.start_time;
+        t.stop_time = timer.stop_time;
+        self.push_bytes(as_bytes(&t));
+        self
+    }
+
+    /// Return the [`Kstat`], as if it had been read from the kernel.
+    pub fn kstat(&self) -> Kstat<'_> {
+        let mut kstat =
+            Kstat::try_from(&self.ks).expect("a fake kstat is built from valid strings");
+        kstat.read = true;
+        kstat
+    }
+
+    /// Return the [`Data`] of the kstat, as if it had been read from the kernel.
+    pub fn data(&self) -> Result<Data<'_>, Error> {
+        self.kstat().data()
+    }
+
+    fn check_type(&self, ty: Type) {
+        assert_eq!(
+            self.ks.ks_type,
+            u8::from(ty),
+            "data of type {} added to a kstat of another type",
+            ty
+        );
+    }
+
+    // Append one element to the data buffer.
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        self.extend(bytes);
+        self.ks.ks_ndata += 1;
+    }
+
+    // Append bytes to the data buffer, returning the offset at which they start.
+    fn extend(&mut self, bytes: &[u8]) -> usize {
+        let start = self.ks.ks_data_size;
+        let end = start + bytes.len();
+        self.data.resize(end.div_ceil(size_of::<u64>()), 0);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                (self.data.as_mut_ptr() as *mut u8).add(start),
+                bytes.len(),
+            )
+        };
+        self.ks.ks_data = self.data.as_mut_ptr() as *mut _;
+        self.ks.ks_data_size = end;
+        start
+    }
+}
+
+// Copy a string into a kstat string array, leaving room for the terminating NUL.
+fn to_array(s: &str) -> [c_char; STRLEN] {
+    assert!(
+        s.len() < STRLEN && !s.as_bytes().contains(&0),
+        "kstat strings are at most {} bytes, without NULs",
+        STRLEN - 1
+    );
+    let mut out = [0; STRLEN];
+    for (dst, src) in out.iter_mut().zip(s.as_bytes()) {
+        *dst = *src as _;
+    }
+    out
+}
+
+// View a plain-old-data FFI struct as bytes.
+fn as_bytes<T: Copy>(t: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(t as *const T as *const u8, size_of::<T>()) }
+}
+
+// The `zfs:0:arcstats` kstat with kstat ID `kid`, holding only its `size`, for tests which
+// replace it across chain updates.
+#[cfg(all(test, feature = "stubs"))]
+pub(crate) fn arcstats(kid: i32, size: u64) -> FakeKstat {
+    FakeKstat::new("zfs", 0, "arcstats", Type::Named)
+        .kid(kid)
+        .named("size", NamedData::UInt64(size))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Named;
+
+    #[test]
+    fn fake_kstat_metadata() {
+        let fake = FakeKstat::new("zfs", 0, "arcstats", Type::Named)
+            .class("misc")
+            .kid(7)
+            .crtime(10)
+            .snaptime(20)
+            .flags(KstatFlags::VIRTUAL);
+        let kstat = fake.kstat();
+        assert_eq!(kstat.ks_module, "zfs");
+        assert_eq!(kstat.ks_instance, 0);
+        assert_eq!(kstat.ks_name, "arcstats");
+        assert_eq!(kstat.ks_class, "misc");
+        assert_eq!(kstat.ks_type, Type::Named);
+        assert_eq!(kstat.ks_crtime, 10);
+        assert_eq!(kstat.ks_snaptime, 20);
+        assert!(kstat.ks_flags.contains(KstatFlags::VIRTUAL));
+        assert!(kstat.is_read());
+        assert_eq!(fake.data().unwrap(), Data::Named(vec![]));
+    }
+
+    #[test]
+    fn fake_raw_kstat() {
+        let fake = FakeKstat::new("m", 0, "raw", Type::Raw)
+            .raw(&[1, 2, 3])
+            .raw(&[4, 5, 6]);
+        assert_eq!(
+            fake.data().unwrap(),
+            Data::Raw(vec![&[1, 2, 3][..], &[4, 5, 6][..]])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn fake_raw_kstat_rejects_uneven_elements() {
+        let _ = FakeKstat::new("m", 0, "raw", Type::Raw)
+            .raw(&[1, 2])
+            .raw(&[3]);
+    }
+
+    #[test]
+    fn fake_named_kstat() {
+        let fake = FakeKstat::new("m", 0, "named", Type::Named)
+            .named("char", NamedData::Char(b"abc"))
+            .named("i32", NamedData::Int32(-1))
+            .named("u32", NamedData::UInt32(2))
+            .named("i64", NamedData::Int64(-3))
+            .named("u64", NamedData::UInt64(4))
+            .named("float", NamedData::Float(0.5))
+            .named("double", NamedData::Double(1.5))
+            .named("string", NamedData::String("hello"));
+        let Data::Named(named) = fake.data().unwrap() else {
+            panic!("expected named data");
+        };
+        let mut chars = [0; NAMED_CHAR_LEN];
+        chars[..3].copy_from_slice(b"abc");
+        let expected = vec![
+            Named {
+                name: "char",
+                value: NamedData::Char(&chars),
+            },
+            Named {
+                name: "i32",
+                value: NamedData::Int32(-1),
+            },
+            Named {
+                name: "u32",
+                value: NamedData::UInt32(2),
+            },
+            Named {
+                name: "i64",
+                value: NamedData::Int64(-3),
+            },
+            Named {
+                name: "u64",
+                value: NamedData::UInt64(4),
+            },
+            Named {
+                name: "float",
+                value: NamedData::Float(0.5),
+            },
+            Named {
+                name: "double",
+                value: NamedData::Double(1.5),
+            },
+            Named {
+                name: "string",
+                value: NamedData::String("hello"),
+            },
+        ];
+        assert_eq!(named, expected);
+    }
+
+    #[test]
+    fn fake_intr_kstat() {
+        let intr = Intr {
+            hard: 1,
+            soft: 2,
+            watchdog: 3,
+            spurious: 4,
+            multisvc: 5,
+        };
+        let fake = FakeKstat::new("m", 0, "intr", Type::Intr).intr(intr);
+        assert_eq!(fake.data().unwrap(), Data::Intr(intr));
+    }
+
+    #[test]
+    fn fake_io_kstat() {
+        let io = Io {
+            nread: 1,
+            nwritten: 2,
+            reads: 3,
+            writes: 4,
+            rlastupdate: 5,
+            ..Default::default()
+        };
+        let fake = FakeKstat::new("m", 0, "io", Type::Io).io(io);
+        assert_eq!(fake.data().unwrap(), Data::Io(io));
+    }
+
+    #[test]
+    fn fake_timer_kstat() {
+        let timer = Timer {
+            name: "t",
+            num_events: 1,
+            elapsed_time: 2,
+            min_time: 3,
+            max_time: 4,
+            start_time: 5,
+            stop_time: 6,
+        };
+        let fake = FakeKstat::new("m", 0, "timer", Type::Timer)
+            .timer(timer)
+            .timer(Timer { name: "u", ..timer });
+        let Data::Timer(timers) = fake.data().unwrap() else {
+            panic!("expected timer data");
+        };
+        assert_eq!(timers, vec![timer, Timer { name: "u", ..timer }]);
+    }
+
+    #[test]
+    fn fake_unknown_kstat() {
+        let fake = FakeKstat::new("m", 0, "future", Type::Unknown(99));
+        assert_eq!(fake.kstat().ks_type, Type::Unknown(99));
+        assert!(matches!(fake.data(), Err(Error::InvalidType(99))));
+    }
+
+    #[test]
+    #[should_panic]
+    fn fake_kstat_rejects_mismatched_data() {
+        let _ = FakeKstat::new("m", 0, "io", Type::Io).named("x", NamedData::UInt64(0));
+    }
+}
@@ -0,0 +1,101 @@
+//! Aggregating I/O statistics across devices.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Data;
+use crate::Error;
+use crate::Io;
+use crate::Type;
+
+/// Sum the statistics of every I/O kstat, such as those of disks and partitions.
+///
+/// The counters and accumulated times of each kstat are summed, wrapping on overflow. The
+/// `wcnt` and `rcnt` queue lengths are summed too, giving the total number of requests in
+/// flight, while `wlastupdate` and `rlastupdate` are the latest of any kstat. Note that some
+/// I/O kstats, such as those of partitions, count requests also counted by their disk.
+///
+/// Kstats which are removed from the chain while being read are skipped.
+pub fn total_io(ctl: &Ctl) -> Result<Io, Error> {
+    let mut total = Io::default();
+    for mut kstat in ctl.iter_by_type(Type::Io) {
+        let io = match ctl.read(&mut kstat) {
+            Ok(Data::Io(io)) => io,
+            Ok(_) => continue,
+            Err(Error::Gone) => continue,
+            Err(e) => return Err(e),
+        };
+        accumulate(&mut total, &io);
+    }
+    Ok(total)
+}
+
+// Add the statistics of `io` to `total`.
+fn accumulate(total: &mut Io, io: &Io) {
+    total.nread = total.nread.wrapping_add(io.nread);
+    total.nwritten = total.nwritten.wrapping_add(io.nwritten);
+    total.reads = total.reads.wrapping_add(io.reads);
+    total.writes = total.writes.wrapping_add(io.writes);
+    total.wtime = total.wtime.wrapping_add(io.wtime);
+    total.wlentime = total.wlentime.wrapping_add(io.wlentime);
+    total.wlastupdate = total.wlastupdate.max(io.wlastupdate);
+    total.rtime = total.rtime.wrapping_add(io.rtime);
+    total.rlentime = total.rlentime.wrapping_add(io.rlentime);
+    total.rlastupdate = total.rlastupdate.max(io.rlastupdate);
+    total.wcnt = total.wcnt.wrapping_add(io.wcnt);
+    total.rcnt = total.rcnt.wrapping_add(io.rcnt);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accumulate_sums_counters() {
+        let io = Io {
+            nread: 10,
+            reads: 1,
+            wlastupdate: 5,
+            wcnt: 2,
+            ..Default::default()
+        };
+        let mut total = Io {
+            nread: u64::MAX,
+            wlastupdate: 7,
+            ..Default::default()
+        };
+        accumulate(&mut total, &io);
+        assert_eq!(total.nread, 9);
+        assert_eq!(total.reads, 1);
+        assert_eq!(total.wlastupdate, 7);
+        assert_eq!(total.wcnt, 2);
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn total_io_covers_each_disk() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let total = total_io(&ctl).expect("Failed to total I/O kstats");
+        let max = ctl
+            .iter_by_type(Type::Io)
+            .filter_map(|mut kstat| match ctl.read(&mut kstat) {
+                Ok(Data::Io(io)) => Some(io.nread),
+                _ => None,
+            })
+            .max()
+            .expect("Expected at least one I/O kstat");
+        assert!(total.nread >= max);
+    }
+}
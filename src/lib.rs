@@ -13,6 +13,9 @@
 //! be of several different types, such as name/value pairs or interrupt statistics. These types
 //! are captured by the [`Data`] enum, which can be read and returned by using the [`Ctl::read`]
 //! method.
+//!
+//! Helpers which extract the statistics of particular subsystems live in their own modules, such
+//! as [`cpu`], [`net`], and [`zfs`].
 
 // Copyright 2023 Oxide Computer Company
 //
@@ -28,53 +31,383 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+#[cfg(feature = "std")]
 use std::cmp::Ord;
+#[cfg(feature = "std")]
 use std::cmp::Ordering;
+#[cfg(feature = "std")]
 use std::cmp::PartialOrd;
-use std::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::ffi::CString;
+#[cfg(feature = "std")]
+use std::hash::Hash;
+#[cfg(feature = "std")]
+use std::hash::Hasher;
+#[cfg(feature = "std")]
+use std::iter::FusedIterator;
+#[cfg(feature = "std")]
 use std::marker::PhantomData;
-use thiserror::Error;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
 
+#[cfg(feature = "std")]
+mod counter;
+#[cfg(feature = "std")]
+pub mod cpu;
+#[cfg(feature = "csv")]
+mod csv;
+#[cfg(feature = "std")]
+mod diff;
+#[cfg(any(feature = "test-util", all(test, feature = "std")))]
+mod fake;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "std")]
+pub mod mem;
+#[cfg(feature = "std")]
+pub mod mib;
+#[cfg(feature = "std")]
+pub mod net;
+#[cfg(feature = "tokio")]
+mod poller;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+#[cfg(feature = "std")]
+mod selector;
+#[cfg(feature = "stubs")]
+pub mod stub;
+#[cfg(feature = "std")]
+mod sync;
 mod sys;
+#[cfg(feature = "std")]
+mod tracked;
+#[cfg(feature = "std")]
+mod unit;
+#[cfg(feature = "std")]
+pub mod vmem;
+#[cfg(feature = "std")]
+pub mod zfs;
+
+#[cfg(feature = "std")]
+pub use counter::CounterHeuristic;
+#[cfg(feature = "csv")]
+pub use csv::named_to_csv;
+#[cfg(feature = "std")]
+pub use diff::{diff_named, ChainDiff, NamedDelta};
+#[cfg(feature = "test-util")]
+pub use fake::FakeKstat;
+#[cfg(feature = "tokio")]
+pub use poller::Poller;
+#[cfg(feature = "prometheus")]
+pub use prometheus::{to_prometheus, to_prometheus_all};
+#[cfg(feature = "std")]
+pub use selector::Selector;
+#[cfg(feature = "std")]
+pub use sync::SyncCtl;
+pub use sys::kstat_ctl_t;
+#[cfg(feature = "std")]
+pub use tracked::Tracked;
+#[cfg(feature = "std")]
+pub use unit::{Unit, UnitRules};
 
 /// Kinds of errors returned by the library.
-#[derive(Debug, Error)]
+#[derive(Debug)]
 pub enum Error {
     /// An attempt to convert a byte-string to a Rust string failed.
-    #[error("The byte-string is not a valid Rust string")]
-    InvalidString,
+    InvalidString {
+        /// The field containing the invalid string.
+        field: StringField,
+    },
+
+    /// A string passed as an argument, such as to [`Ctl::lookup`], contains a NUL byte.
+    NulInArgument,
 
     /// Encountered an invalid kstat type.
-    #[error("Kstat type {0} is invalid")]
     InvalidType(u8),
 
     /// Encountered an invalid named kstat data type.
-    #[error("The named kstat data type {0} is invalid")]
     InvalidNamedType(u8),
 
+    /// Encountered an unknown kstat type name.
+    InvalidTypeName(String),
+
+    /// Encountered an unknown named kstat data type name.
+    InvalidNamedTypeName(String),
+
     /// Encountered a null pointer or empty data.
-    #[error("A null pointer or empty kstat was encountered")]
     NullData,
 
+    /// No kstat matched the requested module, instance, and name.
+    ///
+    /// This is returned by methods such as [`Ctl::read_by_name`], which require exactly one
+    /// kstat to be found.
+    NotFound,
+
+    /// An attempt was made to write a kstat which is not writable.
+    NotWritable,
+
+    /// The data to be written does not match the type or layout of the kstat.
+    DataMismatch,
+
+    /// The kstat is not of the type the operation requires.
+    WrongType { expected: Type, found: Type },
+
+    /// A kstat selector string could not be parsed.
+    InvalidSelector(String),
+
+    /// A glob pattern used to match kstats is invalid.
+    InvalidPattern(String),
+
+    /// The size of a kstat's data does not match the size of the requested type.
+    SizeMismatch { expected: usize, found: usize },
+
+    /// A kstat reports a different number of data items than its type requires.
+    UnexpectedDataShape { expected: usize, found: usize },
+
+    /// A kstat's data is not suitably aligned for the requested type.
+    Unaligned(usize),
+
+    /// The size of a kstat's data is not a whole multiple of the size of its elements.
+    ///
+    /// This usually indicates that the data is corrupt, or that its layout differs from the one
+    /// this crate was built with.
+    MisalignedData { size: usize, element_size: usize },
+
+    /// The kstat no longer exists, and the chain should be updated with [`Ctl::update`].
+    Gone,
+
+    /// The kstat was used with a different [`Ctl`] than the one it was found through.
+    WrongCtl,
+
     /// Error bubbled up from operating on `libkstat`.
-    #[error(transparent)]
-    Io(#[from] std::io::Error),
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidString { field } => write!(
+                f,
+                "The byte-string in the {} is not a valid Rust string",
+                field
+            ),
+            Error::NulInArgument => f.write_str("A string argument contains a NUL byte"),
+            Error::InvalidType(t) => write!(f, "Kstat type {} is invalid", t),
+            Error::InvalidNamedType(t) => write!(f, "The named kstat data type {} is invalid", t),
+            Error::InvalidTypeName(name) => write!(f, "Kstat type name '{}' is invalid", name),
+            Error::InvalidNamedTypeName(name) => {
+                write!(f, "The named kstat data type name '{}' is invalid", name)
+            }
+            Error::NullData => f.write_str("A null pointer or empty kstat was encountered"),
+            Error::NotFound => f.write_str("No matching kstat was found"),
+            Error::NotWritable => f.write_str("The kstat is not writable"),
+            Error::DataMismatch => f.write_str("The data does not match the layout of the kstat"),
+            Error::WrongType { expected, found } => {
+                write!(f, "Expected a kstat of type {}, found {}", expected, found)
+            }
+            Error::InvalidSelector(sel) => write!(f, "Invalid kstat selector '{}'", sel),
+            Error::InvalidPattern(pat) => write!(f, "Invalid kstat pattern '{}'", pat),
+            Error::SizeMismatch { expected, found } => write!(
+                f,
+                "Expected kstat data of {} bytes, found {}",
+                expected, found
+            ),
+            Error::UnexpectedDataShape { expected, found } => {
+                write!(f, "Expected {} kstat data items, found {}", expected, found)
+            }
+            Error::Unaligned(align) => {
+                write!(f, "The kstat data is not aligned to {} bytes", align)
+            }
+            Error::MisalignedData { size, element_size } => write!(
+                f,
+                "Kstat data of {} bytes is not a multiple of the element size of {} bytes",
+                size, element_size
+            ),
+            Error::Gone => f.write_str("The kstat no longer exists"),
+            Error::WrongCtl => f.write_str("The kstat does not belong to this control handle"),
+            #[cfg(feature = "std")]
+            Error::Io(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// The field in which an invalid string was found, for [`Error::InvalidString`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum StringField {
+    /// The module of a kstat.
+    Module,
+    /// The name of a kstat.
+    Name,
+    /// The class of a kstat.
+    Class,
+    /// The name of a named statistic.
+    StatName,
+    /// The value of a `Char` or `String` named statistic.
+    StatValue,
+    /// The name of a timer.
+    TimerName,
+}
+
+impl fmt::Display for StringField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = match self {
+            StringField::Module => "kstat module",
+            StringField::Name => "kstat name",
+            StringField::Class => "kstat class",
+            StringField::StatName => "statistic name",
+            StringField::StatValue => "statistic value",
+            StringField::TimerName => "timer name",
+        };
+        f.write_str(field)
+    }
+}
+
+// Convert the error from a failed operation on a single kstat into an `Error`.
+//
+// `libkstat` reports kstats which have been removed since the chain was last updated with
+// `ENXIO` or `ENOENT`.
+#[cfg(feature = "std")]
+fn kstat_error(err: std::io::Error) -> Error {
+    match err.raw_os_error() {
+        Some(libc::ENXIO) | Some(libc::ENOENT) => Error::Gone,
+        _ => Error::Io(err),
+    }
+}
+
+/// The size of the module, name, and class fields of a kstat, including the terminating NUL.
+pub const STRLEN: usize = sys::KSTAT_STRLEN;
+
+/// The length of the value of a [`NamedData::Char`] statistic, in bytes.
+pub const NAMED_CHAR_LEN: usize = sys::KSTAT_NAMED_CHAR_LEN;
+
+/// Return the current high-resolution time, in nanoseconds, from `gethrtime(3C)`.
+///
+/// This is the same time base as the `ks_crtime` and `ks_snaptime` fields of a [`Kstat`].
+#[cfg(feature = "std")]
+pub fn gethrtime() -> i64 {
+    unsafe { sys::gethrtime() }
+}
+
+/// A mapping from high-resolution time to wall-clock time.
+///
+/// The high-resolution time of [`gethrtime`] counts from an arbitrary point, usually boot, and
+/// is unaffected by changes to the system clock. An `HrtimeClock` captures both clocks at once,
+/// and converts later high-resolution times, such as the `ks_snaptime` of a [`Kstat`], by
+/// their offset from that capture. The result is approximate: it does not account for changes
+/// to the system clock after the capture, nor for drift between the two clocks, and so becomes
+/// less accurate the further a time is from the capture.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct HrtimeClock {
+    hrtime: i64,
+    wall: SystemTime,
+}
+
+#[cfg(feature = "std")]
+impl HrtimeClock {
+    /// Capture the current high-resolution and wall-clock times.
+    pub fn new() -> Self {
+        HrtimeClock {
+            hrtime: gethrtime(),
+            wall: SystemTime::now(),
+        }
+    }
+
+    /// Convert a high-resolution time into an approximate wall-clock time.
+    ///
+    /// This returns `None` if the time is too far from the capture to be represented.
+    pub fn to_system_time(&self, hrtime: i64) -> Option<SystemTime> {
+        let offset = hrtime.checked_sub(self.hrtime)?;
+        let magnitude = Duration::from_nanos(offset.unsigned_abs());
+        if offset >= 0 {
+            self.wall.checked_add(magnitude)
+        } else {
+            self.wall.checked_sub(magnitude)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for HrtimeClock {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// `Ctl` is a handle to the kstat library.
 ///
 /// Users instantiate a control handle and access the kstat's it contains, for example via the
 /// [`Ctl::iter`] method.
-#[derive(Debug)]
+///
+/// A `Ctl` is `Send`, so it may be moved to another thread, but it is not `Sync`. Use a
+/// [`SyncCtl`] to share one between threads.
+#[cfg(feature = "std")]
 pub struct Ctl {
     ctl: *mut sys::kstat_ctl_t,
+    skip_handler: Option<SkipHandler>,
+}
+
+// A callback run for each kstat skipped by `Iter`.
+#[cfg(feature = "std")]
+type SkipHandler = Box<dyn Fn(&SkippedKstat<'_>) + Send + Sync>;
+
+#[cfg(feature = "std")]
+impl fmt::Debug for Ctl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ctl")
+            .field("ctl", &self.ctl)
+            .field("skip_handler", &self.skip_handler.is_some())
+            .finish()
+    }
 }
 
 /// The `Ctl` wraps a raw pointer allocated by the `libkstat(3KSTAT)` library.
 /// This itself isn't thread-safe, but doesn't refer to any thread-local state.
 /// So it's safe to send across threads.
+#[cfg(feature = "std")]
 unsafe impl Send for Ctl {}
 
+#[cfg(feature = "std")]
 impl Ctl {
     /// Create a new `Ctl`.
     pub fn new() -> Result<Self, Error> {
@@ -82,50 +415,566 @@ impl Ctl {
         if ctl.is_null() {
             Err(std::io::Error::last_os_error().into())
         } else {
-            Ok(Ctl { ctl })
+            Ok(Ctl {
+                ctl,
+                skip_handler: None,
+            })
+        }
+    }
+
+    /// Open a new, independent `Ctl`.
+    ///
+    /// The clone is a separate snapshot of the kstat chain, which must be updated independently
+    /// of `self`. This is useful for handing a control handle to another thread.
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        Self::new()
+    }
+
+    /// Create a `Ctl` from a raw handle returned by `kstat_open(3KSTAT)`.
+    ///
+    /// The returned `Ctl` takes ownership of the handle, and closes it when dropped.
+    ///
+    /// # Safety
+    ///
+    /// `ctl` must be a valid, non-null handle returned by `kstat_open(3KSTAT)`, which is not
+    /// closed or used elsewhere for the lifetime of the returned `Ctl`.
+    pub unsafe fn from_raw(ctl: *mut kstat_ctl_t) -> Self {
+        Ctl {
+            ctl,
+            skip_handler: None,
+        }
+    }
+
+    /// Consume the `Ctl`, returning the raw handle without closing it.
+    ///
+    /// The caller becomes responsible for the handle, and must eventually close it with
+    /// `kstat_close(3KSTAT)`, or pass it back to [`Ctl::from_raw`].
+    pub fn into_raw(mut self) -> *mut kstat_ctl_t {
+        let ctl = self.ctl;
+        drop(self.skip_handler.take());
+        std::mem::forget(self);
+        ctl
+    }
+
+    /// Close the `Ctl`, returning any error from `kstat_close(3KSTAT)`.
+    ///
+    /// Dropping a `Ctl` also closes it, but ignores any error.
+    pub fn close(self) -> Result<(), Error> {
+        if unsafe { sys::kstat_close(self.into_raw()) } == -1 {
+            Err(std::io::Error::last_os_error().into())
+        } else {
+            Ok(())
         }
     }
 
+    /// Return the ID of the kstat chain held by this `Ctl`.
+    ///
+    /// The kernel changes the ID whenever kstats are added or removed. This only changes when
+    /// the `Ctl` is updated, so comparing it before and after an update shows whether cached
+    /// kstats need to be re-resolved.
+    pub fn chain_id(&self) -> i32 {
+        unsafe { (*self.ctl).kc_chain_id }
+    }
+
     /// Synchronize this `Ctl` with the kernel's view of the data.
     ///
     /// A `Ctl` is really a snapshot of the kernel's internal list of kstats. This method consumes
     /// and updates a control object, bringing it into sync with the kernel's copy.
     pub fn update(self) -> Result<Self, Error> {
+        self.update_changed().map(|(ctl, _)| ctl)
+    }
+
+    /// Synchronize this `Ctl` with the kernel's view of the data, and report whether the chain
+    /// changed.
+    ///
+    /// This is the same as [`Ctl::update`], but also returns `true` if the kstat chain changed,
+    /// and `false` if it was already up to date. Callers caching kstats should re-resolve them
+    /// when the chain changes.
+    pub fn update_changed(mut self) -> Result<(Self, bool), Error> {
+        let changed = self.update_mut()?;
+        Ok((self, changed))
+    }
+
+    /// Synchronize this `Ctl` with the kernel's view of the data, in place.
+    ///
+    /// This is the same as [`Ctl::update`], but does not consume the control object. It returns
+    /// `true` if the kstat chain changed, and `false` if it was already up to date. Any
+    /// [`Kstat`]s obtained from this `Ctl` may be invalidated if the chain changes.
+    pub fn update_mut(&mut self) -> Result<bool, Error> {
         let kid = unsafe { sys::kstat_chain_update(self.ctl) };
         if kid == -1 {
             Err(std::io::Error::last_os_error().into())
         } else {
-            Ok(self)
+            Ok(kid != 0)
+        }
+    }
+
+    /// Synchronize this `Ctl` with the kernel's view of the data, in place, returning the kstats
+    /// added to and removed from the chain.
+    ///
+    /// This is the same as [`Ctl::update_mut`], but compares the [`KstatId`]s in the chain before
+    /// and after the update. A kstat which is removed and re-added with the same ID between
+    /// updates is not reported.
+    pub fn update_diff(&mut self) -> Result<ChainDiff, Error> {
+        let before = self.iter().map(|kstat| kstat.id()).collect::<BTreeSet<_>>();
+        if !self.update_mut()? {
+            return Ok(ChainDiff::default());
         }
+        let after = self.iter().map(|kstat| kstat.id()).collect::<BTreeSet<_>>();
+        Ok(diff::diff_chain(&before, &after))
     }
 
     /// Return an iterator over the [`Kstat`]s in `self`.
     ///
     /// Note that this will only return `Kstat`s which are successfully read. For example, it will
-    /// ignore those with non-UTF-8 names.
+    /// ignore those with non-UTF-8 names. Use [`Ctl::iter_raw`] to see those as errors, or
+    /// [`Ctl::set_skip_handler`] to be notified of them.
     pub fn iter(&self) -> Iter<'_> {
         Iter {
+            inner: self.iter_lazy(),
+            skip_handler: self.skip_handler.as_ref(),
+        }
+    }
+
+    /// Set a callback to be run for each kstat skipped by [`Ctl::iter`].
+    ///
+    /// This makes it possible to diagnose kstats that are silently dropped, for example those
+    /// with non-UTF-8 names. With the `log` feature enabled, skipped kstats are also logged at
+    /// the debug level.
+    pub fn set_skip_handler(
+        &mut self,
+        handler: impl Fn(&SkippedKstat<'_>) + Send + Sync + 'static,
+    ) {
+        self.skip_handler = Some(Box::new(handler));
+    }
+
+    /// Return the number of entries in the kstat chain.
+    ///
+    /// This walks the chain once, counting every entry, including those that [`Ctl::iter`]
+    /// would skip. The count reflects the chain at the time of the call, and may be invalidated
+    /// by a subsequent update.
+    pub fn count_kstats(&self) -> usize {
+        self.iter_lazy().count()
+    }
+
+    /// Return an iterator over the [`Kstat`]s in `self` of the given type.
+    pub fn iter_by_type(&self, ty: Type) -> impl Iterator<Item = Kstat<'_>> {
+        self.iter().of_type(ty)
+    }
+
+    /// Return an iterator over the [`Kstat`]s in `self`, in sorted order.
+    ///
+    /// The order of the chain is not guaranteed to be stable across updates. This collects the
+    /// kstats, sorts them by their [`Ord`] implementation, and yields them in that order, which
+    /// is useful for reproducible output.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = Kstat<'_>> {
+        let mut kstats = self.iter().collect::<Vec<_>>();
+        kstats.sort();
+        kstats.into_iter()
+    }
+
+    /// Return an iterator over every entry in the kstat chain of `self`.
+    ///
+    /// Unlike [`Ctl::iter`], this yields an error for each entry which cannot be converted into a
+    /// [`Kstat`], such as those with non-UTF-8 names, rather than skipping it.
+    pub fn iter_raw(&self) -> IterRaw<'_> {
+        IterRaw {
+            inner: self.iter_lazy(),
+        }
+    }
+
+    /// Return an iterator over every entry in the kstat chain of `self`, without decoding them.
+    ///
+    /// The [`LazyKstat`]s yielded by this iterator decode their names only when asked, which
+    /// is cheaper than [`Ctl::iter`] when only some kstats are of interest, for example when
+    /// filtering by type. `Ctl::iter` checks and converts the module, name, and class of every
+    /// kstat to strings, while [`LazyKstat::ks_type`] reads only the type code. Use
+    /// [`LazyKstat::to_kstat`] to convert those of interest to a [`Kstat`].
+    pub fn iter_lazy(&self) -> IterLazy<'_> {
+        IterLazy {
+            ctl: self.ctl,
             kstat: unsafe { (*self.ctl).kc_chain },
             _d: PhantomData,
         }
     }
 
+    /// Return an iterator over the kstats in `self`, decoding their names lossily.
+    ///
+    /// Unlike [`Ctl::iter`], kstats whose module, name, or class are not valid UTF-8 are not
+    /// skipped. Instead, any invalid sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn iter_lossy(&self) -> impl Iterator<Item = KstatLossy<'_>> {
+        self.iter_lazy()
+            .filter_map(|kstat| KstatLossy::try_from(kstat.ks).ok())
+    }
+
+    /// Find [`Kstat`]s by shell-style glob patterns on their module, instance, and/or name.
+    ///
+    /// Each pattern may contain `*`, matching any sequence of characters, and `?`, matching any
+    /// single character. The instance pattern is matched against the decimal representation of
+    /// the instance, and may contain only digits and wildcards. If a pattern is `None`, any
+    /// `Kstat` matches.
+    pub fn filter_glob(
+        &self,
+        module: Option<&str>,
+        instance: Option<&str>,
+        name: Option<&str>,
+    ) -> Result<impl Iterator<Item = Kstat<'_>>, Error> {
+        let module = module.map(selector::Pattern::new);
+        let instance = instance.map(selector::Pattern::new_instance).transpose()?;
+        let name = name.map(selector::Pattern::new);
+        Ok(self.iter().filter(move |kstat| {
+            module.as_ref().is_none_or(|p| p.matches(kstat.ks_module))
+                && instance
+                    .as_ref()
+                    .is_none_or(|p| p.matches(&kstat.ks_instance.to_string()))
+                && name.as_ref().is_none_or(|p| p.matches(kstat.ks_name))
+        }))
+    }
+
+    /// Find [`Kstat`]s whose class is exactly `class`, such as `disk` or `net`.
+    pub fn filter_class<'a>(&'a self, class: &'a str) -> impl Iterator<Item = Kstat<'a>> {
+        self.iter().filter(move |kstat| kstat.ks_class == class)
+    }
+
+    /// Find [`Kstat`]s matching the module, instance, and name of a [`Selector`].
+    ///
+    /// The statistic portion of the selector is not used, since it refers to the data within a
+    /// kstat, rather than the kstat itself.
+    pub fn select<'a>(&'a self, sel: &'a Selector) -> impl Iterator<Item = Kstat<'a>> {
+        self.filter(
+            sel.module.as_deref(),
+            sel.instance.map_or(Instance::Any, Instance::Num),
+            sel.name.as_deref(),
+        )
+    }
+
+    /// Read every kstat in the chain, returning a snapshot of their data.
+    ///
+    /// The snapshot owns its data, and so may outlive `self`. Kstats which fail to be read are
+    /// skipped, and with the `log` feature each is logged as a warning. The entries are sorted
+    /// by their [`KstatId`].
+    pub fn snapshot(&self) -> Result<Vec<(KstatId, OwnedData)>, Error> {
+        let mut snapshot = self
+            .iter()
+            .filter_map(|mut kstat| match self.read(&mut kstat) {
+                Ok(data) => Some((kstat.id(), data.to_owned())),
+                Err(e) => {
+                    #[cfg(feature = "log")]
+                    log::warn!(
+                        "skipping kstat {}:{}:{}, which failed to read: {}",
+                        kstat.ks_module,
+                        kstat.ks_instance,
+                        kstat.ks_name,
+                        e
+                    );
+                    #[cfg(not(feature = "log"))]
+                    let _ = e;
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(snapshot)
+    }
+
+    /// Return an iterator over each module in the chain, along with all of its kstats.
+    ///
+    /// The modules are yielded in order of their names, and the kstats of each module in chain
+    /// order. The kstats of a module are grouped together even if they are not adjacent in the
+    /// chain.
+    pub fn iter_modules(&self) -> impl Iterator<Item = (&str, Vec<Kstat<'_>>)> {
+        let mut modules = BTreeMap::<_, Vec<_>>::new();
+        for kstat in self.iter() {
+            modules.entry(kstat.ks_module).or_default().push(kstat);
+        }
+        modules.into_iter()
+    }
+
+    /// Read every kstat in the chain, grouping their data by module, instance, and name.
+    ///
+    /// As with [`Ctl::snapshot`], kstats which fail to be read are skipped, and logged as
+    /// warnings with the `log` feature.
+    pub fn grouped(&self) -> Result<Grouped, Error> {
+        let mut groups = Grouped::new();
+        for (id, data) in self.snapshot()? {
+            groups
+                .entry(id.module)
+                .or_default()
+                .entry(id.instance)
+                .or_default()
+                .insert(id.name, data);
+        }
+        Ok(groups)
+    }
+
+    /// Read each of the kstats identified by `ids`, with a single walk of the chain.
+    ///
+    /// The results are returned in the same order as `ids`. [`Error::NotFound`] is returned for
+    /// any ID which does not match a kstat in the chain.
+    pub fn read_many(&self, ids: &[KstatId]) -> Vec<(KstatId, Result<OwnedData, Error>)> {
+        let mut indices = HashMap::<_, Vec<_>>::new();
+        for (i, id) in ids.iter().enumerate() {
+            indices
+                .entry((id.module.as_str(), id.instance, id.name.as_str()))
+                .or_default()
+                .push(i);
+        }
+        let mut results = ids.iter().map(|_| None).collect::<Vec<_>>();
+        for mut kstat in self.iter() {
+            let key = (kstat.ks_module, kstat.ks_instance, kstat.ks_name);
+            if let Some(indices) = indices.get(&key) {
+                for &i in indices.iter() {
+                    results[i] = Some(self.read(&mut kstat).map(|data| data.to_owned()));
+                }
+            }
+        }
+        ids.iter()
+            .cloned()
+            .zip(results)
+            .map(|(id, result)| (id, result.unwrap_or(Err(Error::NotFound))))
+            .collect()
+    }
+
+    /// Read every kstat in the chain, yielding each along with the result of reading it.
+    ///
+    /// Unlike [`Ctl::snapshot`], kstats which fail to be read are not skipped, and their errors
+    /// are yielded instead. The data is owned, so it remains valid as the iterator advances.
+    pub fn read_all(&self) -> impl Iterator<Item = (Kstat<'_>, Result<OwnedData, Error>)> {
+        self.iter().map(move |mut kstat| {
+            let data = self.read(&mut kstat).map(|data| data.to_owned());
+            (kstat, data)
+        })
+    }
+
+    /// Read every kstat in the chain, yielding the identifier and owned data of each.
+    ///
+    /// This reads each kstat only as the iterator advances. A kstat which fails to be read yields
+    /// its error, rather than ending the iteration. Use [`Ctl::read_all`] to also see which
+    /// kstat each error is for.
+    pub fn read_iter(&self) -> impl Iterator<Item = Result<(KstatId, OwnedData), Error>> + '_ {
+        self.read_all()
+            .map(|(kstat, data)| data.map(|data| (kstat.id(), data)))
+    }
+
+    // Read each of `kstats`, and decode its data with `decode`.
+    //
+    // Kstats which `decode` rejects, or which are removed from the chain while being read, are
+    // skipped, and logged if the `log` feature is enabled. Any other failure to read is returned.
+    pub(crate) fn decode_each<'a, T>(
+        &self,
+        kstats: impl Iterator<Item = Kstat<'a>>,
+        mut decode: impl FnMut(&Kstat<'a>, &Data<'a>) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        let mut out = Vec::new();
+        for mut kstat in kstats {
+            let data = match self.read(&mut kstat) {
+                Ok(data) => data,
+                Err(Error::Gone) => continue,
+                Err(e) => return Err(e),
+            };
+            match decode(&kstat, &data) {
+                Ok(item) => out.push(item),
+                Err(e) => {
+                    #[cfg(feature = "log")]
+                    log::warn!(
+                        "skipping kstat {}:{}:{}, which failed to decode: {}",
+                        kstat.ks_module,
+                        kstat.ks_instance,
+                        kstat.ks_name,
+                        e
+                    );
+                    #[cfg(not(feature = "log"))]
+                    let _ = e;
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Read a [`Kstat`] into a reusable buffer, returning a view of the data for it.
+    ///
+    /// This is the same as [`Ctl::read`], but the elements of `Raw`, `Named`, and `Timer` data
+    /// are stored in `buf` rather than a newly-allocated vector. Reusing one buffer across many
+    /// reads avoids repeated allocations in polling loops.
+    pub fn read_into<'a, 'b>(
+        &self,
+        kstat: &mut Kstat<'a>,
+        buf: &'b mut DataBuf<'a>,
+    ) -> Result<DataRef<'a, 'b>, Error> {
+        kstat.read(self.ctl)?;
+        kstat.data_into(buf)
+    }
+
     /// Read a [`Kstat`], returning the data for it.
+    ///
+    /// If the kstat has been removed since the chain was last updated, [`Error::Gone`] is
+    /// returned. If the kstat was found through a different `Ctl`, [`Error::WrongCtl`] is
+    /// returned.
+    ///
+    /// A kstat built directly from a `kstat_t` with `TryFrom` is not associated with any
+    /// `Ctl`, so this check is skipped for it. The caller must ensure such a kstat is on the
+    /// chain of this `Ctl`.
     pub fn read<'a>(&self, kstat: &mut Kstat<'a>) -> Result<Data<'a>, Error> {
         kstat.read(self.ctl)?;
         kstat.data()
     }
 
+    /// Read a [`Kstat`], returning its new snapshot time along with its data.
+    ///
+    /// This is the same as [`Ctl::read`], but pairs the data with the `ks_snaptime` at which it
+    /// was read, which is the timestamp to use when computing rates from it.
+    pub fn read_with_time<'a>(&self, kstat: &mut Kstat<'a>) -> Result<(i64, Data<'a>), Error> {
+        let data = self.read(kstat)?;
+        Ok((kstat.ks_snaptime, data))
+    }
+
+    /// Read a named [`Kstat`], returning only the statistics whose names are in `names`.
+    ///
+    /// The statistics are returned in the order they appear in the kstat. Only the selected
+    /// statistics are decoded, which avoids allocating for every statistic of wide kstats.
+    /// [`Error::WrongType`] is returned if the kstat is not a named kstat.
+    pub fn read_named_subset<'a>(
+        &self,
+        kstat: &mut Kstat<'a>,
+        names: &[&str],
+    ) -> Result<Vec<Named<'a>>, Error> {
+        kstat.read(self.ctl)?;
+        kstat.named_subset(names)
+    }
+
+    /// Write data to a [`Kstat`].
+    ///
+    /// The kstat must be writable, and `data` must have the same type and layout as the kstat's
+    /// current data, for example the same named statistics in the same order. The values of
+    /// `String` named statistics cannot be changed. The kstat is read first if it has not been
+    /// already.
+    #[cfg(feature = "write")]
+    pub fn write(&self, kstat: &mut Kstat<'_>, data: &Data<'_>) -> Result<(), Error> {
+        kstat.check_ctl(self.ctl)?;
+        if !kstat.ks_flags.is_writable() {
+            return Err(Error::NotWritable);
+        }
+        if !kstat.read {
+            kstat.read(self.ctl)?;
+        }
+        let ks = unsafe { kstat.ks.as_ref() }.ok_or(Error::NullData)?;
+        if ks.ks_data.is_null() {
+            return Err(Error::NullData);
+        }
+        let mut buf = sys::kstat_data_copy(ks);
+        encode_data(ks, kstat.ks_type, data, &mut buf)?;
+        if unsafe { sys::kstat_write(self.ctl, kstat.ks, buf.as_mut_ptr().cast()) } == -1 {
+            Err(kstat_error(std::io::Error::last_os_error()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Look up a [`Kstat`] by module, instance, and name, and read its data.
+    ///
+    /// This combines [`Ctl::lookup`] and [`Ctl::read`], returning [`Error::NotFound`] if no
+    /// matching kstat exists.
+    pub fn read_by_name(&self, module: &str, instance: i32, name: &str) -> Result<Data<'_>, Error> {
+        let mut kstat = self
+            .lookup(Some(module), Instance::Num(instance), Some(name))?
+            .ok_or(Error::NotFound)?;
+        self.read(&mut kstat)
+    }
+
+    /// Look up and read the kstat with the given ID, updating the chain and retrying if the kstat
+    /// is gone.
+    ///
+    /// A kstat which is removed and recreated, for example when the chain changes while it is
+    /// being polled, fails to read with [`Error::Gone`] until the chain is updated. On that
+    /// error, this updates the chain with [`Ctl::update_mut`] and looks the kstat up again, up
+    /// to `max_retries` times, before returning the error. [`Error::NotFound`] is returned if
+    /// the kstat is no longer in the chain after an update.
+    ///
+    /// This takes the ID of the kstat rather than a [`Kstat`], since updating the chain
+    /// invalidates every `Kstat` borrowed from it.
+    pub fn read_retry(&mut self, id: &KstatId, max_retries: usize) -> Result<Data<'_>, Error> {
+        let mut retries = 0;
+        loop {
+            let result = self
+                .lookup(Some(&id.module), Instance::Num(id.instance), Some(&id.name))?
+                .ok_or(Error::NotFound)?
+                .read(self.ctl);
+            match result {
+                Ok(()) => break,
+                Err(Error::Gone) if retries < max_retries => {
+                    retries += 1;
+                    self.update_mut()?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        // The chain has not been updated since the kstat was read, so it need not be read again.
+        let mut kstat = self
+            .lookup(Some(&id.module), Instance::Num(id.instance), Some(&id.name))?
+            .ok_or(Error::NotFound)?;
+        kstat.read = true;
+        kstat.data()
+    }
+
+    /// Look up a single [`Kstat`] by module, instance, and/or name.
+    ///
+    /// This uses `kstat_lookup(3KSTAT)`, which returns the first kstat in the chain matching all
+    /// of the supplied fields. If a field is `None`, or the instance is [`Instance::Any`], it
+    /// matches any value. `Ok(None)` is returned if there is no matching kstat.
+    pub fn lookup(
+        &self,
+        module: Option<&str>,
+        instance: Instance,
+        name: Option<&str>,
+    ) -> Result<Option<Kstat<'_>>, Error> {
+        fn to_cstring(s: Option<&str>) -> Result<Option<CString>, Error> {
+            s.map(|s| CString::new(s).map_err(|_| Error::NulInArgument))
+                .transpose()
+        }
+        // `kstat_lookup` treats an instance of -1 as a wildcard, so search for it directly.
+        if instance == Instance::Num(-1) {
+            return Ok(self.iter().find(|kstat| {
+                module.is_none_or(|m| m == kstat.ks_module)
+                    && kstat.ks_instance == -1
+                    && name.is_none_or(|n| n == kstat.ks_name)
+            }));
+        }
+        let module = to_cstring(module)?;
+        let name = to_cstring(name)?;
+        let kstat = unsafe {
+            sys::kstat_lookup(
+                self.ctl,
+                module.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
+                instance.as_option().unwrap_or(-1),
+                name.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
+            )
+        };
+        if let Some(kstat) = unsafe { kstat.as_ref() } {
+            let mut kstat = Kstat::try_from(kstat)?;
+            kstat.ctl = self.ctl;
+            Ok(Some(kstat))
+        } else {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOENT) {
+                Ok(None)
+            } else {
+                Err(kstat_error(err))
+            }
+        }
+    }
+
     /// Find [`Kstat`]s by module, instance, and/or name.
     ///
-    /// If a field is `None`, any matching `Kstat` is returned.
+    /// If a field is `None`, or the instance is [`Instance::Any`], any matching `Kstat` is
+    /// returned.
     pub fn filter<'a>(
         &'a self,
         module: Option<&'a str>,
-        instance: Option<i32>,
+        instance: Instance,
         name: Option<&'a str>,
     ) -> impl Iterator<Item = Kstat<'a>> {
-        self.iter().filter_map(move |kstat| {
+        let instance = instance.as_option();
+        self.iter().filter(move |kstat| {
             fn should_include<T>(inner: &T, cmp: &Option<T>) -> bool
             where
                 T: PartialEq,
@@ -136,18 +985,30 @@ impl Ctl {
                     true // Include if this comparator is None
                 }
             }
-            let include = should_include(&kstat.ks_module, &module)
+            // All supplied comparators must match.
+            should_include(&kstat.ks_module, &module)
                 && should_include(&kstat.ks_instance, &instance)
-                && should_include(&kstat.ks_name, &name);
-            if include {
-                Some(kstat)
-            } else {
-                None
-            }
+                && should_include(&kstat.ks_name, &name)
         })
     }
+
+    /// Find the first [`Kstat`] matching the module, instance, and/or name.
+    ///
+    /// This matches kstats as [`Ctl::filter`] does, but returns [`Error::NotFound`] if no kstat
+    /// matches.
+    pub fn find<'a>(
+        &'a self,
+        module: Option<&'a str>,
+        instance: Instance,
+        name: Option<&'a str>,
+    ) -> Result<Kstat<'a>, Error> {
+        self.filter(module, instance, name)
+            .next()
+            .ok_or(Error::NotFound)
+    }
 }
 
+#[cfg(feature = "std")]
 impl Drop for Ctl {
     fn drop(&mut self) {
         unsafe {
@@ -156,121 +1017,786 @@ impl Drop for Ctl {
     }
 }
 
+#[cfg(feature = "std")]
+impl<'a> IntoIterator for &'a Ctl {
+    type Item = Kstat<'a>;
+    type IntoIter = Iter<'a>;
+
+    /// Return an iterator over the [`Kstat`]s in the `Ctl`, as [`Ctl::iter`] does.
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A kstat skipped by [`Ctl::iter`], passed to the handler set with [`Ctl::set_skip_handler`].
+#[cfg(feature = "std")]
 #[derive(Debug)]
+pub struct SkippedKstat<'a> {
+    /// The raw bytes of the module of the kstat.
+    pub module: &'a [u8],
+    /// The instance of the kstat.
+    pub instance: i32,
+    /// The raw bytes of the name of the kstat.
+    pub name: &'a [u8],
+    /// The reason the kstat was skipped.
+    pub reason: &'a Error,
+}
+
+/// An iterator over the [`Kstat`]s in a [`Ctl`], created with [`Ctl::iter`].
+///
+/// The iterators over a `Ctl` walk the kstat chain it owns, which is freed and rebuilt when the
+/// `Ctl` is updated. They, and the `Kstat`s they yield, borrow the `Ctl`, so the borrow checker
+/// rejects updating it while any of them are alive.
+///
+/// Once an iterator over a `Ctl` returns `None`, it always does.
+#[cfg(feature = "std")]
 pub struct Iter<'a> {
-    kstat: *mut sys::kstat_t,
-    _d: PhantomData<&'a ()>,
+    inner: IterLazy<'a>,
+    skip_handler: Option<&'a SkipHandler>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> fmt::Debug for Iter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Iter")
+            .field("inner", &self.inner)
+            .field("skip_handler", &self.skip_handler.is_some())
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Iter<'a> {
+    /// Adapt this iterator to yield only the [`Kstat`]s of the given type.
+    pub fn of_type(self, ty: Type) -> impl Iterator<Item = Kstat<'a>> {
+        self.filter(move |kstat| kstat.ks_type == ty)
+    }
 }
 
+#[cfg(feature = "std")]
 impl<'a> Iterator for Iter<'a> {
     type Item = Kstat<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if let Some(ks) = unsafe { self.kstat.as_ref() } {
-                self.kstat = unsafe { *self.kstat }.ks_next;
-                if let Ok(ks) = Kstat::try_from(ks) {
-                    break Some(ks);
-                }
-                // continue to next kstat
-            } else {
-                break None;
+        // Skip any kstats that can't be converted.
+        for lazy in self.inner.by_ref() {
+            match lazy.to_kstat() {
+                Ok(kstat) => return Some(kstat),
+                Err(e) => report_skipped(self.skip_handler, &lazy, &e),
             }
         }
+        None
     }
 }
 
-unsafe impl<'a> Send for Iter<'a> {}
+#[cfg(feature = "std")]
+impl<'a> FusedIterator for Iter<'a> {}
 
-/// `Kstat` represents a single kernel statistic.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub struct Kstat<'a> {
-    /// The creation time of the stat, in nanoseconds.
-    pub ks_crtime: i64,
-    /// The time of the last update, in nanoseconds.
-    pub ks_snaptime: i64,
-    /// The module of the kstat.
-    pub ks_module: &'a str,
-    /// The instance of the kstat.
-    pub ks_instance: i32,
-    /// The name of the kstat.
+// Report that `kstat` was skipped by `Iter` because of `reason`.
+#[cfg(feature = "std")]
+fn report_skipped(handler: Option<&SkipHandler>, kstat: &LazyKstat<'_>, reason: &Error) {
+    let skipped = SkippedKstat {
+        module: sys::array_to_bytes(&kstat.ks.ks_module, StringField::Module)
+            .unwrap_or(sys::array_as_bytes(&kstat.ks.ks_module)),
+        instance: kstat.ks.ks_instance,
+        name: sys::array_to_bytes(&kstat.ks.ks_name, StringField::Name)
+            .unwrap_or(sys::array_as_bytes(&kstat.ks.ks_name)),
+        reason,
+    };
+    #[cfg(feature = "log")]
+    log::debug!(
+        "skipping kstat {}:{}:{}: {}",
+        String::from_utf8_lossy(skipped.module),
+        skipped.instance,
+        String::from_utf8_lossy(skipped.name),
+        reason,
+    );
+    if let Some(handler) = handler {
+        handler(&skipped);
+    }
+}
+
+/// An iterator over every entry in the kstat chain of a [`Ctl`], created with
+/// [`Ctl::iter_raw`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IterRaw<'a> {
+    inner: IterLazy<'a>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Iterator for IterRaw<'a> {
+    type Item = Result<Kstat<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|kstat| kstat.to_kstat())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> FusedIterator for IterRaw<'a> {}
+
+/// An iterator over every entry in the kstat chain of a [`Ctl`], created with
+/// [`Ctl::iter_lazy`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IterLazy<'a> {
+    ctl: *mut sys::kstat_ctl_t,
+    kstat: *mut sys::kstat_t,
+    _d: PhantomData<&'a ()>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Iterator for IterLazy<'a> {
+    type Item = LazyKstat<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ks = unsafe { self.kstat.as_ref() }?;
+        self.kstat = ks.ks_next;
+        Some(LazyKstat { ctl: self.ctl, ks })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> FusedIterator for IterLazy<'a> {}
+
+#[cfg(feature = "std")]
+unsafe impl<'a> Send for IterLazy<'a> {}
+
+/// An entry in the kstat chain, whose names are decoded on demand.
+///
+/// This is produced by [`Ctl::iter_lazy`]. The accessors decode the underlying data each time
+/// they are called, so callers needing all fields repeatedly should convert to a [`Kstat`] with
+/// [`LazyKstat::to_kstat`].
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct LazyKstat<'a> {
+    ctl: *mut sys::kstat_ctl_t,
+    ks: &'a sys::kstat_t,
+}
+
+#[cfg(feature = "std")]
+unsafe impl<'a> Send for LazyKstat<'a> {}
+
+#[cfg(feature = "std")]
+impl<'a> LazyKstat<'a> {
+    /// Return the creation time of the stat, in nanoseconds.
+    pub fn crtime(&self) -> i64 {
+        self.ks.ks_crtime
+    }
+
+    /// Return the time of the last update, in nanoseconds.
+    pub fn snaptime(&self) -> i64 {
+        self.ks.ks_snaptime
+    }
+
+    /// Return the module of the kstat.
+    pub fn module(&self) -> Result<&'a str, Error> {
+        sys::array_to_cstr(&self.ks.ks_module, StringField::Module)
+    }
+
+    /// Return the instance of the kstat.
+    pub fn instance(&self) -> i32 {
+        self.ks.ks_instance
+    }
+
+    /// Return the name of the kstat.
+    pub fn name(&self) -> Result<&'a str, Error> {
+        sys::array_to_cstr(&self.ks.ks_name, StringField::Name)
+    }
+
+    /// Return the class of the kstat.
+    pub fn class(&self) -> Result<&'a str, Error> {
+        sys::array_to_cstr(&self.ks.ks_class, StringField::Class)
+    }
+
+    /// Return the type of the kstat.
+    pub fn ks_type(&self) -> Result<Type, Error> {
+        Type::try_from(self.ks.ks_type)
+    }
+
+    /// Decode all the fields of this entry, returning a [`Kstat`].
+    pub fn to_kstat(&self) -> Result<Kstat<'a>, Error> {
+        let mut kstat = Kstat::try_from(self.ks)?;
+        kstat.ctl = self.ctl;
+        Ok(kstat)
+    }
+}
+
+/// The data of many kstats, keyed by module, then instance, then name.
+///
+/// See [`Ctl::grouped`].
+#[cfg(feature = "std")]
+pub type Grouped = BTreeMap<String, BTreeMap<i32, BTreeMap<String, OwnedData>>>;
+
+/// A plain-old-data type, which may be read directly out of the data buffer of a raw kstat.
+///
+/// This is implemented for the integer and floating-point primitives, and arrays of them. It may be
+/// implemented for a `#[repr(C)]` structure matching the layout of a raw kstat, such as
+/// `unix:0:var`, to read it with [`Kstat::raw_as`] or [`Data::raw_elements`].
+///
+/// # Safety
+///
+/// Any bit pattern of `size_of::<Self>()` bytes must be a valid value of the type. This rules out
+/// types containing references, pointers which are dereferenced, `bool`, `char`, or enums.
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// `Kstat` represents a single kernel statistic.
+///
+/// The identifying fields are public, but the getter methods such as [`Kstat::module`] are the
+/// preferred way to access them, as the fields may become private in a future release.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct Kstat<'a> {
+    /// The creation time of the stat, in nanoseconds.
+    pub ks_crtime: i64,
+    /// The time of the last update, in nanoseconds.
+    pub ks_snaptime: i64,
+    /// The module of the kstat.
+    pub ks_module: &'a str,
+    /// The instance of the kstat.
+    pub ks_instance: i32,
+    /// The name of the kstat.
     pub ks_name: &'a str,
     /// The type of the kstat.
     pub ks_type: Type,
     /// The class of the kstat.
     pub ks_class: &'a str,
+    /// The flags of the kstat.
+    pub ks_flags: KstatFlags,
     ks: *mut sys::kstat_t,
+    // The `Ctl` this was found through, or null if it was not found through one.
+    ctl: *mut sys::kstat_ctl_t,
+    read: bool,
+}
+
+// Kstats are equal when they order equally, so that equality agrees with `Ord`. Two reads of
+// the same kstat are equal, though their snapshot times differ.
+#[cfg(feature = "std")]
+impl<'a> PartialEq for Kstat<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
 }
 
+#[cfg(feature = "std")]
+impl<'a> Eq for Kstat<'a> {}
+
+#[cfg(feature = "std")]
 impl<'a> PartialOrd for Kstat<'a> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(
-            self.ks_class
-                .cmp(other.ks_class)
-                .then_with(|| self.ks_module.cmp(other.ks_module))
-                .then_with(|| self.ks_instance.cmp(&other.ks_instance))
-                .then_with(|| self.ks_name.cmp(other.ks_name))
-                .then_with(|| self.ks_class.cmp(other.ks_name)),
-        )
+        Some(self.cmp(other))
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> Ord for Kstat<'a> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.ks_class
+            .cmp(other.ks_class)
+            .then_with(|| self.ks_module.cmp(other.ks_module))
+            .then_with(|| self.ks_instance.cmp(&other.ks_instance))
+            .then_with(|| self.ks_name.cmp(other.ks_name))
+            .then_with(|| self.ks_crtime.cmp(&other.ks_crtime))
     }
 }
 
+#[cfg(feature = "std")]
 unsafe impl<'a> Send for Kstat<'a> {}
 
+#[cfg(feature = "std")]
 impl<'a> Kstat<'a> {
+    // Check that this kstat was found through the `Ctl` with handle `ctl`.
+    //
+    // Kstats built with `TryFrom` have no handle, and pass unchecked.
+    fn check_ctl(&self, ctl: *mut sys::kstat_ctl_t) -> Result<(), Error> {
+        if !self.ctl.is_null() && self.ctl != ctl {
+            Err(Error::WrongCtl)
+        } else {
+            Ok(())
+        }
+    }
+
     fn read(&mut self, ctl: *mut sys::kstat_ctl_t) -> Result<(), Error> {
+        self.check_ctl(ctl)?;
         if unsafe { sys::kstat_read(ctl, self.ks, std::ptr::null_mut()) } == -1 {
-            Err(std::io::Error::last_os_error().into())
+            Err(kstat_error(std::io::Error::last_os_error()))
         } else {
             self.ks_snaptime = unsafe { (*self.ks).ks_snaptime };
+            self.read = true;
             Ok(())
         }
     }
 
+    /// Read this kstat again through `ctl`, returning its new snapshot time.
+    ///
+    /// This is useful for polling only the timestamp of a long-lived kstat. The refreshed data
+    /// is available through [`Kstat::raw_bytes`], or may be decoded with [`Ctl::read`].
+    pub fn refresh(&mut self, ctl: &Ctl) -> Result<i64, Error> {
+        self.read(ctl.ctl)?;
+        Ok(self.ks_snaptime)
+    }
+
+    /// Return `true` if the kstat may be written, from its flags.
+    pub fn is_writable(&self) -> bool {
+        self.ks_flags.is_writable()
+    }
+
+    /// Reset the statistics of this kstat to zero, by writing it through `ctl`.
+    ///
+    /// This reads the kstat, then writes back its data with each numeric value zeroed, and each
+    /// `Char` value cleared to all NUL bytes. The names of named statistics and timers are
+    /// preserved, as are `String` values, which can't be written. [`Error::NotWritable`] is
+    /// returned if the kstat is not writable.
+    #[cfg(feature = "write")]
+    pub fn reset(&self, ctl: &Ctl) -> Result<(), Error> {
+        self.check_ctl(ctl.ctl)?;
+        if !self.is_writable() {
+            return Err(Error::NotWritable);
+        }
+        let mut kstat = *self;
+        let data = ctl.read(&mut kstat)?;
+        let zeros;
+        let zeroed = match data {
+            Data::Raw(raw) => {
+                zeros = vec![0; raw.first().map_or(0, |r| r.len())];
+                Data::Raw(vec![&zeros[..]; raw.len()])
+            }
+            Data::Named(named) => Data::Named(
+                named
+                    .into_iter()
+                    .map(|n| Named {
+                        name: n.name,
+                        value: match n.value {
+                            NamedData::Char(_) => NamedData::Char(&[]),
+                            NamedData::Int32(_) => NamedData::Int32(0),
+                            NamedData::UInt32(_) => NamedData::UInt32(0),
+                            NamedData::Int64(_) => NamedData::Int64(0),
+                            NamedData::UInt64(_) => NamedData::UInt64(0),
+                            NamedData::Float(_) => NamedData::Float(0.0),
+                            NamedData::Double(_) => NamedData::Double(0.0),
+                            NamedData::String(s) => NamedData::String(s),
+                        },
+                    })
+                    .collect(),
+            ),
+            Data::Intr(_) => Data::Intr(Intr::default()),
+            Data::Io(_) => Data::Io(Io::default()),
+            Data::Timer(timers) => Data::Timer(
+                timers
+                    .into_iter()
+                    .map(|t| Timer {
+                        name: t.name,
+                        num_events: 0,
+                        elapsed_time: 0,
+                        min_time: 0,
+                        max_time: 0,
+                        start_time: 0,
+                        stop_time: 0,
+                    })
+                    .collect(),
+            ),
+            Data::Null => Data::Null,
+        };
+        ctl.write(&mut kstat, &zeroed)
+    }
+
+    /// Return the module of the kstat.
+    pub fn module(&self) -> &'a str {
+        self.ks_module
+    }
+
+    /// Return the instance of the kstat.
+    pub fn instance(&self) -> i32 {
+        self.ks_instance
+    }
+
+    /// Return the name of the kstat.
+    pub fn name(&self) -> &'a str {
+        self.ks_name
+    }
+
+    /// Return the class of the kstat.
+    pub fn class(&self) -> &'a str {
+        self.ks_class
+    }
+
+    /// Return `true` if the values of this kstat are likely monotonic counters.
+    ///
+    /// This is a best-effort guess using the default [`CounterHeuristic`]. The kstat must be in
+    /// a counter class and, if it is a named kstat which has been read, at least one of its
+    /// statistics must have a counter suffix. Use [`CounterHeuristic::is_counter`] to classify
+    /// individual statistics, or to customize the heuristic.
+    pub fn is_counter_class(&self) -> bool {
+        let heuristic = CounterHeuristic::default_ref();
+        if !heuristic.is_counter_class(self.ks_class) {
+            return false;
+        }
+        match self.ks_type {
+            Type::Named if self.read => matches!(
+                self.data(),
+                Ok(Data::Named(named)) if named.iter().any(|n| heuristic.is_counter_name(n.name))
+            ),
+            _ => true,
+        }
+    }
+
+    /// Return the module, instance, and name identifying this kstat.
+    pub fn id(&self) -> KstatId {
+        KstatId::new(self.ks_module, self.ks_instance, self.ks_name)
+    }
+
+    /// Read this kstat through `ctl`, and format it for debugging along with its decoded data.
+    ///
+    /// The `Debug` implementation of a `Kstat` shows only a pointer to its data. This reads a
+    /// copy of the kstat and pretty-prints its identifying fields and data. If the read fails,
+    /// the error is shown in place of the data.
+    ///
+    /// The fields of `self`, such as [`Kstat::is_read`] and its snapshot time, are not updated.
+    /// However, the copy shares the kernel's data buffer with `self` and any other copies, so the
+    /// read replaces the data they see with the newly read values.
+    pub fn debug_with_data(&self, ctl: &Ctl) -> String {
+        let mut kstat = *self;
+        let data = ctl.read(&mut kstat);
+        format!(
+            "{:#?}",
+            DebugWithData {
+                kstat: &kstat,
+                data: &data
+            }
+        )
+    }
+
+    /// Return the time since this kstat's data was last updated, in nanoseconds.
+    pub fn age_ns(&self) -> i64 {
+        gethrtime() - self.ks_snaptime
+    }
+
+    /// Return the creation time of this kstat, as the time since boot.
+    ///
+    /// Negative times are clamped to zero.
+    pub fn crtime_duration(&self) -> Duration {
+        Duration::from_nanos(self.ks_crtime.max(0) as u64)
+    }
+
+    /// Return the time of the last update of this kstat, as the time since boot.
+    ///
+    /// Negative times are clamped to zero.
+    pub fn snaptime_duration(&self) -> Duration {
+        Duration::from_nanos(self.ks_snaptime.max(0) as u64)
+    }
+
+    /// Return the system uptime at the last update of this kstat.
+    ///
+    /// This is the same as [`Kstat::snaptime_duration`].
+    pub fn uptime_at_snapshot(&self) -> Duration {
+        self.snaptime_duration()
+    }
+
+    /// Return the kstat ID of this kstat.
+    ///
+    /// Kstat IDs are unique within a snapshot of the chain, and are not reused when kstats are
+    /// removed, so they identify a kstat across chain updates more reliably than its name.
+    pub fn kid(&self) -> i32 {
+        unsafe { self.ks.as_ref() }.map_or(-1, |ks| ks.ks_kid)
+    }
+
+    /// Return the size of this kstat's data buffer, in bytes.
+    ///
+    /// For kstats whose size may change, such as named kstats with string values, this is only
+    /// meaningful after the kstat has been read.
+    pub fn data_size(&self) -> usize {
+        unsafe { self.ks.as_ref() }.map_or(0, |ks| ks.ks_data_size)
+    }
+
+    /// Return the number of data elements in this kstat.
+    ///
+    /// As with [`Kstat::data_size`], this is only meaningful for dynamically-sized kstats after
+    /// the kstat has been read.
+    pub fn ndata(&self) -> u32 {
+        unsafe { self.ks.as_ref() }.map_or(0, |ks| ks.ks_ndata)
+    }
+
+    /// Return the entire data buffer of this kstat as a single slice of bytes.
+    ///
+    /// Unlike [`Data::Raw`], the buffer is not split into `ks_ndata` items. This is useful for
+    /// raw kstats backed by a single C structure, which callers can cast to their own
+    /// `#[repr(C)]` type. The kstat must have been read, and the returned slice is only valid
+    /// until it is read again.
+    pub fn raw_bytes(&self) -> Result<&[u8], Error> {
+        let ks = unsafe { self.ks.as_ref() }.ok_or(Error::NullData)?;
+        if !self.read && ks.ks_data.is_null() {
+            return Err(Error::NullData);
+        }
+        Ok(sys::kstat_data_bytes(ks))
+    }
+
+    /// Reinterpret the data buffer of this kstat as a single value of type `T`.
+    ///
+    /// The size of the buffer must be exactly the size of `T`. This is useful for raw kstats
+    /// backed by a C structure, such as `unix:0:var`, described by a type implementing [`Pod`].
+    pub fn raw_as<T: Pod>(&self) -> Result<&T, Error> {
+        let bytes = self.raw_bytes()?;
+        if bytes.len() != std::mem::size_of::<T>() {
+            return Err(Error::SizeMismatch {
+                expected: std::mem::size_of::<T>(),
+                found: bytes.len(),
+            });
+        }
+        Ok(&self.raw_as_slice::<T>()?[0])
+    }
+
+    /// Reinterpret the data buffer of this kstat as a slice of values of type `T`.
+    ///
+    /// The size of the buffer must be a multiple of the size of `T`, and the buffer must be
+    /// suitably aligned for `T`.
+    pub fn raw_as_slice<T: Pod>(&self) -> Result<&[T], Error> {
+        let bytes = self.raw_bytes()?;
+        let size = std::mem::size_of::<T>();
+        if size == 0 || bytes.len() % size != 0 {
+            return Err(Error::SizeMismatch {
+                expected: bytes.len().next_multiple_of(size.max(1)),
+                found: bytes.len(),
+            });
+        }
+        if bytes.is_empty() {
+            return Ok(&[]);
+        }
+        let align = std::mem::align_of::<T>();
+        if bytes.as_ptr().align_offset(align) != 0 {
+            return Err(Error::Unaligned(align));
+        }
+        // Safety: the buffer is aligned and sized for `T`, and `T: Pod` accepts any bit pattern.
+        Ok(unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast(), bytes.len() / size) })
+    }
+
+    /// Return `true` if this kstat has been read since it was produced from the chain.
+    ///
+    /// A `Kstat` obtained from [`Ctl::iter`] or similar methods has not been read, and there may
+    /// be no data available for it until [`Ctl::read`] is called.
+    pub fn is_read(&self) -> bool {
+        self.read
+    }
+
     fn data(&self) -> Result<Data<'a>, Error> {
-        let ks = unsafe { self.ks.as_ref() }.ok_or_else(|| Error::NullData)?;
+        let ks = unsafe { self.ks.as_ref() }.ok_or(Error::NullData)?;
+        if !self.read && ks.ks_data.is_null() {
+            return Err(Error::NullData);
+        }
+        decode_data(ks, self.ks_type)
+    }
+
+    fn named_subset(&self, names: &[&str]) -> Result<Vec<Named<'a>>, Error> {
+        let ks = unsafe { self.ks.as_ref() }.ok_or(Error::NullData)?;
+        if !self.read && ks.ks_data.is_null() {
+            return Err(Error::NullData);
+        }
+        if self.ks_type != Type::Named {
+            return Err(Error::WrongType {
+                expected: Type::Named,
+                found: self.ks_type,
+            });
+        }
+        let mut subset = Vec::new();
+        for named in sys::kstat_data_named(ks)?.iter() {
+            // Compare the raw bytes of each name, so that only the selected names need be valid
+            // strings.
+            let name = sys::array_as_bytes(&named.name);
+            let name = name.split(|&b| b == 0).next().unwrap_or(name);
+            if names.iter().any(|n| n.as_bytes() == name) {
+                subset.push(Named::try_from(named)?);
+            }
+        }
+        Ok(subset)
+    }
+
+    fn data_into<'b>(&self, buf: &'b mut DataBuf<'a>) -> Result<DataRef<'a, 'b>, Error> {
+        let ks = unsafe { self.ks.as_ref() }.ok_or(Error::NullData)?;
+        if !self.read && ks.ks_data.is_null() {
+            return Err(Error::NullData);
+        }
         match self.ks_type {
-            Type::Raw => Ok(Data::Raw(sys::kstat_data_raw(ks))),
-            Type::Named => Ok(Data::Named(
-                sys::kstat_data_named(ks)
-                    .iter()
-                    .map(Named::try_from)
-                    .collect::<Result<_, _>>()?,
-            )),
-            Type::Intr => Ok(Data::Intr(Intr::from(sys::kstat_data_intr(ks)))),
-            Type::Io => Ok(Data::Io(Io::from(sys::kstat_data_io(ks)))),
-            Type::Timer => Ok(Data::Timer(
-                sys::kstat_data_timer(ks)
-                    .iter()
-                    .map(Timer::try_from)
-                    .collect::<Result<_, _>>()?,
-            )),
+            Type::Raw => {
+                sys::kstat_data_raw_into(ks, &mut buf.raw)?;
+                Ok(DataRef::Raw(&buf.raw))
+            }
+            Type::Named => {
+                buf.named.clear();
+                for named in sys::kstat_data_named(ks)?.iter() {
+                    buf.named.push(Named::try_from(named)?);
+                }
+                Ok(DataRef::Named(&buf.named))
+            }
+            Type::Intr => Ok(DataRef::Intr(Intr::from(sys::kstat_data_intr(ks)?))),
+            Type::Io => Ok(DataRef::Io(Io::from(sys::kstat_data_io(ks)?))),
+            Type::Timer => {
+                buf.timer.clear();
+                for timer in sys::kstat_data_timer(ks)?.iter() {
+                    buf.timer.push(Timer::try_from(timer)?);
+                }
+                Ok(DataRef::Timer(&buf.timer))
+            }
+            Type::Unknown(code) => Err(Error::InvalidType(code)),
+        }
+    }
+}
+
+// Decode the data buffer of `ks`, as a kstat of type `ty`.
+fn decode_data(ks: &sys::kstat_t, ty: Type) -> Result<Data<'_>, Error> {
+    match ty {
+        Type::Raw => Ok(Data::Raw(sys::kstat_data_raw(ks)?)),
+        Type::Named => Ok(Data::Named(
+            sys::kstat_data_named(ks)?
+                .iter()
+                .map(Named::try_from)
+                .collect::<Result<_, _>>()?,
+        )),
+        Type::Intr => Ok(Data::Intr(Intr::from(sys::kstat_data_intr(ks)?))),
+        Type::Io => Ok(Data::Io(Io::from(sys::kstat_data_io(ks)?))),
+        Type::Timer => Ok(Data::Timer(
+            sys::kstat_data_timer(ks)?
+                .iter()
+                .map(Timer::try_from)
+                .collect::<Result<_, _>>()?,
+        )),
+        Type::Unknown(code) => Err(Error::InvalidType(code)),
+    }
+}
+
+// Encode `data` into `buf`, a copy of the data buffer of `ks`.
+#[cfg(feature = "write")]
+fn encode_data(ks: &sys::kstat_t, ty: Type, data: &Data<'_>, buf: &mut [u64]) -> Result<(), Error> {
+    // Reinterpret the front of the buffer as `count` items of type `T`.
+    fn items<T>(buf: &mut [u64], count: usize) -> Result<&mut [T], Error> {
+        if count * std::mem::size_of::<T>() > std::mem::size_of_val(buf) {
+            return Err(Error::DataMismatch);
+        }
+        Ok(unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr().cast(), count) })
+    }
+
+    match (ty, data) {
+        (Type::Raw, Data::Raw(raw)) => {
+            let len = raw.iter().map(|r| r.len()).sum::<usize>();
+            if len != ks.ks_data_size {
+                return Err(Error::DataMismatch);
+            }
+            let dst = items::<u8>(buf, len)?;
+            for (dst, src) in dst.iter_mut().zip(raw.iter().flat_map(|r| r.iter())) {
+                *dst = *src;
+            }
+        }
+        (Type::Named, Data::Named(named)) => {
+            let count = sys::kstat_data_named(ks)?.len();
+            let dst = items::<sys::kstat_named_t>(buf, count)?;
+            if dst.len() != named.len() {
+                return Err(Error::DataMismatch);
+            }
+            for (dst, src) in dst.iter_mut().zip(named.iter()) {
+                let current = Named::try_from(&*dst)?;
+                if current.name != src.name || current.data_type() != src.data_type() {
+                    return Err(Error::DataMismatch);
+                }
+                match src.value {
+                    NamedData::Char(c) => {
+                        let mut charc = [0; NAMED_CHAR_LEN];
+                        if c.len() > charc.len() {
+                            return Err(Error::DataMismatch);
+                        }
+                        charc[..c.len()].copy_from_slice(c);
+                        dst.value.charc = charc;
+                    }
+                    NamedData::Int32(i) => dst.value.i32 = i,
+                    NamedData::UInt32(u) => dst.value.ui32 = u,
+                    NamedData::Int64(i) => dst.value.i64 = i,
+                    NamedData::UInt64(u) => dst.value.ui64 = u,
+                    NamedData::Float(f) => dst.value.f = f,
+                    NamedData::Double(d) => dst.value.d = d,
+                    NamedData::String(s) => {
+                        if !matches!(current.value, NamedData::String(c) if c == s) {
+                            return Err(Error::DataMismatch);
+                        }
+                    }
+                }
+            }
+        }
+        (Type::Intr, Data::Intr(intr)) => {
+            let dst = &mut items::<sys::kstat_intr_t>(buf, 1)?[0];
+            dst.intr_hard = intr.hard;
+            dst.intr_soft = intr.soft;
+            dst.intr_watchdog = intr.watchdog;
+            dst.intr_spurious = intr.spurious;
+            dst.intr_multisvc = intr.multisvc;
+        }
+        (Type::Io, Data::Io(io)) => {
+            let dst = &mut items::<sys::kstat_io_t>(buf, 1)?[0];
+            dst.nread = io.nread;
+            dst.nwritten = io.nwritten;
+            dst.reads = io.reads;
+            dst.writes = io.writes;
+            dst.wtime = io.wtime;
+            dst.wlentime = io.wlentime;
+            dst.wlastupdate = io.wlastupdate;
+            dst.rtime = io.rtime;
+            dst.rlentime = io.rlentime;
+            dst.rlastupdate = io.rlastupdate;
+            dst.wcnt = io.wcnt;
+            dst.rcnt = io.rcnt;
+        }
+        (Type::Timer, Data::Timer(timers)) => {
+            let dst = items::<sys::kstat_timer_t>(buf, ks.ks_ndata as usize)?;
+            if dst.len() != timers.len() {
+                return Err(Error::DataMismatch);
+            }
+            for (dst, src) in dst.iter_mut().zip(timers.iter()) {
+                if sys::array_to_cstr(&dst.name, StringField::TimerName)? != src.name {
+                    return Err(Error::DataMismatch);
+                }
+                dst.num_events = src.num_events as _;
+                dst.elapsed_time = src.elapsed_time;
+                dst.min_time = src.min_time;
+                dst.max_time = src.max_time;
+                dst.start_time = src.start_time;
+                dst.stop_time = src.stop_time;
+            }
         }
+        _ => return Err(Error::DataMismatch),
     }
+    Ok(())
 }
 
+/// The resulting kstat is not associated with a [`Ctl`], so reading it cannot check that it
+/// belongs to the handle it is read through.
+#[cfg(feature = "std")]
 impl<'a> TryFrom<&'a sys::kstat_t> for Kstat<'a> {
     type Error = Error;
     fn try_from(k: &'a sys::kstat_t) -> Result<Self, Self::Error> {
         Ok(Kstat {
             ks_crtime: k.ks_crtime,
             ks_snaptime: k.ks_snaptime,
-            ks_module: sys::array_to_cstr(&k.ks_module)?,
+            ks_module: sys::array_to_cstr(&k.ks_module, StringField::Module)?,
             ks_instance: k.ks_instance,
-            ks_name: sys::array_to_cstr(&k.ks_name)?,
+            ks_name: sys::array_to_cstr(&k.ks_name, StringField::Name)?,
             ks_type: Type::try_from(k.ks_type)?,
-            ks_class: sys::array_to_cstr(&k.ks_name)?,
+            ks_class: sys::array_to_cstr(&k.ks_class, StringField::Class)?,
+            ks_flags: KstatFlags::from(k.ks_flags as u8),
             ks: k as *const _ as *mut _,
+            ctl: std::ptr::null_mut(),
+            read: false,
         })
     }
 }
 
+#[cfg(feature = "std")]
 impl<'a> TryFrom<&'a *mut sys::kstat_t> for Kstat<'a> {
     type Error = Error;
     fn try_from(k: &'a *mut sys::kstat_t) -> Result<Self, Self::Error> {
@@ -282,75 +1808,640 @@ impl<'a> TryFrom<&'a *mut sys::kstat_t> for Kstat<'a> {
     }
 }
 
-/// The type of a kstat.
-#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
-pub enum Type {
-    Raw,
-    Named,
-    Intr,
-    Io,
-    Timer,
+// Formats a kstat along with its data, for `Kstat::debug_with_data`.
+#[cfg(feature = "std")]
+struct DebugWithData<'a, 'b> {
+    kstat: &'b Kstat<'a>,
+    data: &'b Result<Data<'a>, Error>,
 }
 
-impl TryFrom<u8> for Type {
-    type Error = Error;
-    fn try_from(t: u8) -> Result<Self, Self::Error> {
-        match t {
-            sys::KSTAT_TYPE_RAW => Ok(Type::Raw),
-            sys::KSTAT_TYPE_NAMED => Ok(Type::Named),
-            sys::KSTAT_TYPE_INTR => Ok(Type::Intr),
-            sys::KSTAT_TYPE_IO => Ok(Type::Io),
-            sys::KSTAT_TYPE_TIMER => Ok(Type::Timer),
-            other => Err(Self::Error::InvalidType(other)),
+#[cfg(feature = "std")]
+impl<'a, 'b> fmt::Debug for DebugWithData<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Kstat");
+        s.field("ks_module", &self.kstat.ks_module)
+            .field("ks_instance", &self.kstat.ks_instance)
+            .field("ks_name", &self.kstat.ks_name)
+            .field("ks_class", &self.kstat.ks_class)
+            .field("ks_type", &self.kstat.ks_type)
+            .field("ks_crtime", &self.kstat.ks_crtime)
+            .field("ks_snaptime", &self.kstat.ks_snaptime);
+        match self.data {
+            Ok(data) => s.field("data", data),
+            Err(e) => s.field("error", e),
+        };
+        s.finish()
+    }
+}
+
+/// The instance of a kstat to search for, in [`Ctl::filter`] and [`Ctl::lookup`].
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Instance {
+    /// Match any instance.
+    #[default]
+    Any,
+    /// Match exactly this instance.
+    Num(i32),
+}
+
+#[cfg(feature = "std")]
+impl Instance {
+    /// Return the instance matched exactly, or `None` if this matches any instance.
+    pub fn as_option(&self) -> Option<i32> {
+        match self {
+            Instance::Any => None,
+            Instance::Num(n) => Some(*n),
         }
     }
 }
 
-/// The data type of a single name/value pair of a named kstat.
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub enum NamedType {
-    Char,
-    Int32,
-    UInt32,
-    Int64,
-    UInt64,
-    String,
+/// The module, instance, and name which identify a kstat.
+///
+/// This owns its data, so it may be used as a key identifying a kstat across updates of the chain.
+/// It is formatted as `module:instance:name`, as used by the `kstat(8)` command.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KstatId {
+    /// The module of the kstat.
+    pub module: String,
+    /// The instance of the kstat.
+    pub instance: i32,
+    /// The name of the kstat.
+    pub name: String,
 }
 
-impl TryFrom<u8> for NamedType {
-    type Error = Error;
-    fn try_from(t: u8) -> Result<Self, Self::Error> {
-        match t {
-            sys::KSTAT_DATA_CHAR => Ok(NamedType::Char),
-            sys::KSTAT_DATA_INT32 => Ok(NamedType::Int32),
-            sys::KSTAT_DATA_UINT32 => Ok(NamedType::UInt32),
-            sys::KSTAT_DATA_INT64 => Ok(NamedType::Int64),
-            sys::KSTAT_DATA_UINT64 => Ok(NamedType::UInt64),
-            sys::KSTAT_DATA_STRING => Ok(NamedType::String),
-            other => Err(Self::Error::InvalidNamedType(other)),
+#[cfg(feature = "std")]
+impl KstatId {
+    /// Create a new `KstatId`.
+    pub fn new(module: impl Into<String>, instance: i32, name: impl Into<String>) -> Self {
+        Self {
+            module: module.into(),
+            instance,
+            name: name.into(),
         }
     }
 }
 
-/// Data from a single kstat.
-#[derive(Clone, Debug)]
-pub enum Data<'a> {
-    Raw(Vec<&'a [u8]>),
-    Named(Vec<Named<'a>>),
-    Intr(Intr),
-    Io(Io),
-    Timer(Vec<Timer<'a>>),
-    Null,
+#[cfg(feature = "std")]
+impl fmt::Display for KstatId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.module, self.instance, self.name)
+    }
 }
 
-/// An I/O kernel statistic
-#[derive(Debug, Clone, Copy)]
-pub struct Io {
-    pub nread: u64,
-    pub nwritten: u64,
-    pub reads: u32,
-    pub writes: u32,
-    pub wtime: i64,
+/// `KstatLossy` describes a single kernel statistic, with its names decoded lossily.
+///
+/// This is produced by [`Ctl::iter_lossy`], and is identical to [`Kstat`] except that its string
+/// fields may contain replacement characters if the kernel's names are not valid UTF-8.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KstatLossy<'a> {
+    /// The creation time of the stat, in nanoseconds.
+    pub ks_crtime: i64,
+    /// The time of the last update, in nanoseconds.
+    pub ks_snaptime: i64,
+    /// The module of the kstat.
+    pub ks_module: Cow<'a, str>,
+    /// The instance of the kstat.
+    pub ks_instance: i32,
+    /// The name of the kstat.
+    pub ks_name: Cow<'a, str>,
+    /// The type of the kstat.
+    pub ks_type: Type,
+    /// The class of the kstat.
+    pub ks_class: Cow<'a, str>,
+    /// The flags of the kstat.
+    pub ks_flags: KstatFlags,
+}
+
+#[cfg(feature = "std")]
+impl<'a> TryFrom<&'a sys::kstat_t> for KstatLossy<'a> {
+    type Error = Error;
+    fn try_from(k: &'a sys::kstat_t) -> Result<Self, Self::Error> {
+        Ok(KstatLossy {
+            ks_crtime: k.ks_crtime,
+            ks_snaptime: k.ks_snaptime,
+            ks_module: sys::array_to_cstr_lossy(&k.ks_module, StringField::Module)?,
+            ks_instance: k.ks_instance,
+            ks_name: sys::array_to_cstr_lossy(&k.ks_name, StringField::Name)?,
+            ks_type: Type::try_from(k.ks_type)?,
+            ks_class: sys::array_to_cstr_lossy(&k.ks_class, StringField::Class)?,
+            ks_flags: KstatFlags::from(k.ks_flags as u8),
+        })
+    }
+}
+
+/// The flags describing a kstat, from its `ks_flags` field.
+#[derive(Debug, Clone, Copy, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct KstatFlags(u8);
+
+impl KstatFlags {
+    /// The kstat's data is not allocated by the kstat framework.
+    pub const VIRTUAL: KstatFlags = KstatFlags(sys::KSTAT_FLAG_VIRTUAL);
+    /// The kstat's data size varies.
+    pub const VAR_SIZE: KstatFlags = KstatFlags(sys::KSTAT_FLAG_VAR_SIZE);
+    /// The kstat may be written with `kstat_write(3KSTAT)`.
+    pub const WRITABLE: KstatFlags = KstatFlags(sys::KSTAT_FLAG_WRITABLE);
+    /// The kstat persists across its provider being removed.
+    pub const PERSISTENT: KstatFlags = KstatFlags(sys::KSTAT_FLAG_PERSISTENT);
+    /// The kstat is persistent, and its provider is currently absent.
+    pub const DORMANT: KstatFlags = KstatFlags(sys::KSTAT_FLAG_DORMANT);
+    /// The kstat's data is temporarily unavailable.
+    pub const INVALID: KstatFlags = KstatFlags(sys::KSTAT_FLAG_INVALID);
+    /// The kstat's named string data may be longer than a `Char`.
+    pub const LONGSTRINGS: KstatFlags = KstatFlags(sys::KSTAT_FLAG_LONGSTRINGS);
+
+    /// Return the raw flag bits.
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Return `true` if all the flags in `other` are set in `self`.
+    pub const fn contains(&self, other: KstatFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Return `true` if the kstat may be written.
+    pub const fn is_writable(&self) -> bool {
+        self.contains(Self::WRITABLE)
+    }
+
+    /// Return `true` if the kstat's data is virtual.
+    pub const fn is_virtual(&self) -> bool {
+        self.contains(Self::VIRTUAL)
+    }
+
+    /// Return `true` if the kstat is persistent.
+    pub const fn is_persistent(&self) -> bool {
+        self.contains(Self::PERSISTENT)
+    }
+}
+
+impl From<u8> for KstatFlags {
+    fn from(bits: u8) -> Self {
+        KstatFlags(bits)
+    }
+}
+
+impl core::ops::BitOr for KstatFlags {
+    type Output = KstatFlags;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        KstatFlags(self.0 | rhs.0)
+    }
+}
+
+/// The type of a kstat.
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Type {
+    Raw,
+    Named,
+    Intr,
+    Io,
+    Timer,
+    /// A type not known to this crate, with its raw type code.
+    ///
+    /// Kstats of unknown types are still enumerated, but their data cannot be read. These
+    /// display as `unknown(N)`, which parses back to the same type.
+    Unknown(u8),
+}
+
+impl TryFrom<u8> for Type {
+    type Error = Error;
+    fn try_from(t: u8) -> Result<Self, Self::Error> {
+        match t {
+            sys::KSTAT_TYPE_RAW => Ok(Type::Raw),
+            sys::KSTAT_TYPE_NAMED => Ok(Type::Named),
+            sys::KSTAT_TYPE_INTR => Ok(Type::Intr),
+            sys::KSTAT_TYPE_IO => Ok(Type::Io),
+            sys::KSTAT_TYPE_TIMER => Ok(Type::Timer),
+            other => Ok(Type::Unknown(other)),
+        }
+    }
+}
+
+impl From<Type> for u8 {
+    fn from(t: Type) -> Self {
+        match t {
+            Type::Raw => sys::KSTAT_TYPE_RAW,
+            Type::Named => sys::KSTAT_TYPE_NAMED,
+            Type::Intr => sys::KSTAT_TYPE_INTR,
+            Type::Io => sys::KSTAT_TYPE_IO,
+            Type::Timer => sys::KSTAT_TYPE_TIMER,
+            Type::Unknown(code) => code,
+        }
+    }
+}
+
+impl Type {
+    /// Return the canonical lowercase name of this type.
+    ///
+    /// All unknown types are named `"unknown"`.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Type::Raw => "raw",
+            Type::Named => "named",
+            Type::Intr => "intr",
+            Type::Io => "io",
+            Type::Timer => "timer",
+            Type::Unknown(_) => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Unknown(code) => write!(f, "unknown({})", code),
+            _ => f.write_str(self.as_str()),
+        }
+    }
+}
+
+impl FromStr for Type {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Type::Raw),
+            "named" => Ok(Type::Named),
+            "intr" => Ok(Type::Intr),
+            "io" => Ok(Type::Io),
+            "timer" => Ok(Type::Timer),
+            other => other
+                .strip_prefix("unknown(")
+                .and_then(|rest| rest.strip_suffix(')'))
+                .and_then(|code| code.parse::<u8>().ok())
+                .map(|code| Type::try_from(code).unwrap_or(Type::Unknown(code)))
+                .ok_or_else(|| Error::InvalidTypeName(other.to_string())),
+        }
+    }
+}
+
+impl TryFrom<&str> for Type {
+    type Error = Error;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The data type of a single name/value pair of a named kstat.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NamedType {
+    Char,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float,
+    Double,
+    /// A C `long`, which is decoded as [`NamedData::Int64`].
+    Long,
+    /// A C `unsigned long`, which is decoded as [`NamedData::UInt64`].
+    ULong,
+    String,
+}
+
+impl TryFrom<u8> for NamedType {
+    type Error = Error;
+    fn try_from(t: u8) -> Result<Self, Self::Error> {
+        match t {
+            sys::KSTAT_DATA_CHAR => Ok(NamedType::Char),
+            sys::KSTAT_DATA_INT32 => Ok(NamedType::Int32),
+            sys::KSTAT_DATA_UINT32 => Ok(NamedType::UInt32),
+            sys::KSTAT_DATA_INT64 => Ok(NamedType::Int64),
+            sys::KSTAT_DATA_UINT64 => Ok(NamedType::UInt64),
+            sys::KSTAT_DATA_FLOAT => Ok(NamedType::Float),
+            sys::KSTAT_DATA_DOUBLE => Ok(NamedType::Double),
+            sys::KSTAT_DATA_LONG => Ok(NamedType::Long),
+            sys::KSTAT_DATA_ULONG => Ok(NamedType::ULong),
+            sys::KSTAT_DATA_STRING => Ok(NamedType::String),
+            other => Err(Self::Error::InvalidNamedType(other)),
+        }
+    }
+}
+
+impl From<NamedType> for u8 {
+    fn from(t: NamedType) -> Self {
+        match t {
+            NamedType::Char => sys::KSTAT_DATA_CHAR,
+            NamedType::Int32 => sys::KSTAT_DATA_INT32,
+            NamedType::UInt32 => sys::KSTAT_DATA_UINT32,
+            NamedType::Int64 => sys::KSTAT_DATA_INT64,
+            NamedType::UInt64 => sys::KSTAT_DATA_UINT64,
+            NamedType::Float => sys::KSTAT_DATA_FLOAT,
+            NamedType::Double => sys::KSTAT_DATA_DOUBLE,
+            NamedType::Long => sys::KSTAT_DATA_LONG,
+            NamedType::ULong => sys::KSTAT_DATA_ULONG,
+            NamedType::String => sys::KSTAT_DATA_STRING,
+        }
+    }
+}
+
+impl NamedType {
+    /// Return the canonical lowercase name of this type.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            NamedType::Char => "char",
+            NamedType::Int32 => "int32",
+            NamedType::UInt32 => "uint32",
+            NamedType::Int64 => "int64",
+            NamedType::UInt64 => "uint64",
+            NamedType::Float => "float",
+            NamedType::Double => "double",
+            NamedType::Long => "long",
+            NamedType::ULong => "ulong",
+            NamedType::String => "string",
+        }
+    }
+}
+
+impl fmt::Display for NamedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NamedType {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "char" => Ok(NamedType::Char),
+            "int32" => Ok(NamedType::Int32),
+            "uint32" => Ok(NamedType::UInt32),
+            "int64" => Ok(NamedType::Int64),
+            "uint64" => Ok(NamedType::UInt64),
+            "float" => Ok(NamedType::Float),
+            "double" => Ok(NamedType::Double),
+            "long" => Ok(NamedType::Long),
+            "ulong" => Ok(NamedType::ULong),
+            "string" => Ok(NamedType::String),
+            other => Err(Error::InvalidNamedTypeName(other.to_string())),
+        }
+    }
+}
+
+impl TryFrom<&str> for NamedType {
+    type Error = Error;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Data from a single kstat.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Data<'a> {
+    Raw(Vec<&'a [u8]>),
+    Named(Vec<Named<'a>>),
+    Intr(Intr),
+    Io(Io),
+    Timer(Vec<Timer<'a>>),
+    Null,
+}
+
+impl<'a> Data<'a> {
+    /// Copy this data into an [`OwnedData`], which does not borrow from the [`Ctl`].
+    pub fn to_owned(&self) -> OwnedData {
+        OwnedData::from(self)
+    }
+
+    /// Decode a data buffer captured from a kstat of type `ty` with `ndata` elements.
+    ///
+    /// This parses a copy of the `ks_data` buffer of a kstat, for example one extracted from a
+    /// crash dump, without any live [`Ctl`]. The buffer must have the layout of the running
+    /// system. The values of `String` named statistics are stored outside the buffer and can't
+    /// be followed, so they decode as empty strings.
+    pub fn decode_raw(ty: Type, ndata: u32, bytes: &[u8]) -> Result<OwnedData, Error> {
+        // Copy the bytes into a buffer suitably aligned for any of the kstat data types.
+        let mut buf = alloc::vec![0u64; bytes.len().div_ceil(core::mem::size_of::<u64>())];
+        unsafe {
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), buf.as_mut_ptr().cast(), bytes.len())
+        };
+        let mut ks: sys::kstat_t = unsafe { core::mem::zeroed() };
+        ks.ks_type = u8::from(ty);
+        ks.ks_ndata = ndata;
+        ks.ks_data = buf.as_mut_ptr().cast();
+        ks.ks_data_size = bytes.len();
+        if ty == Type::Named {
+            for named in sys::kstat_data_named_mut(&mut ks)? {
+                if named.data_type == sys::KSTAT_DATA_STRING {
+                    named.value.str = sys::NamedStr {
+                        addr: core::ptr::null(),
+                        len: 0,
+                    };
+                }
+            }
+        }
+        Ok(decode_data(&ks, ty)?.to_owned())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Data<'a> {
+    /// Return the [`Type`] of kstat this data was read from, or `None` for `Null` data.
+    pub fn kind(&self) -> Option<Type> {
+        match self {
+            Data::Raw(_) => Some(Type::Raw),
+            Data::Named(_) => Some(Type::Named),
+            Data::Intr(_) => Some(Type::Intr),
+            Data::Io(_) => Some(Type::Io),
+            Data::Timer(_) => Some(Type::Timer),
+            Data::Null => None,
+        }
+    }
+
+    /// Return the number of data elements.
+    ///
+    /// This is the number of items for `Raw`, `Named`, and `Timer` data, 1 for `Intr` and `Io`
+    /// data, and 0 for `Null`.
+    pub fn len(&self) -> usize {
+        match self {
+            Data::Raw(r) => r.len(),
+            Data::Named(n) => n.len(),
+            Data::Intr(_) | Data::Io(_) => 1,
+            Data::Timer(t) => t.len(),
+            Data::Null => 0,
+        }
+    }
+
+    /// Return `true` if there are no data elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return a hash of the values in this data, for detecting changes between reads.
+    ///
+    /// Timestamps which change on every update, such as the `wlastupdate` and `rlastupdate`
+    /// fields of `Io` data, and the start and stop times of `Timer` data, are not included. The
+    /// hash is stable within a process, but may differ between builds of this crate.
+    pub fn content_hash(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+        std::mem::discriminant(self).hash(&mut h);
+        match self {
+            Data::Raw(raw) => raw.hash(&mut h),
+            Data::Named(named) => {
+                for n in named.iter() {
+                    n.name.hash(&mut h);
+                    std::mem::discriminant(&n.value).hash(&mut h);
+                    match n.value {
+                        NamedData::Char(c) => c.hash(&mut h),
+                        NamedData::Int32(i) => i.hash(&mut h),
+                        NamedData::UInt32(u) => u.hash(&mut h),
+                        NamedData::Int64(i) => i.hash(&mut h),
+                        NamedData::UInt64(u) => u.hash(&mut h),
+                        NamedData::Float(f) => f.to_bits().hash(&mut h),
+                        NamedData::Double(d) => d.to_bits().hash(&mut h),
+                        NamedData::String(s) => s.hash(&mut h),
+                    }
+                }
+            }
+            Data::Intr(intr) => [
+                intr.hard,
+                intr.soft,
+                intr.watchdog,
+                intr.spurious,
+                intr.multisvc,
+            ]
+            .hash(&mut h),
+            Data::Io(io) => {
+                (io.nread, io.nwritten, io.reads, io.writes).hash(&mut h);
+                (io.wtime, io.wlentime, io.rtime, io.rlentime).hash(&mut h);
+                (io.wcnt, io.rcnt).hash(&mut h);
+            }
+            Data::Timer(timers) => {
+                for t in timers.iter() {
+                    (t.name, t.num_events).hash(&mut h);
+                    (t.elapsed_time, t.min_time, t.max_time).hash(&mut h);
+                }
+            }
+            Data::Null => {}
+        }
+        h.finish()
+    }
+
+    /// Reinterpret each element of `Raw` data as a value of type `T`.
+    ///
+    /// Each element must be exactly the size of `T`, and suitably aligned. The result is empty
+    /// for any other variant.
+    pub fn raw_elements<T: Pod>(&self) -> Result<Vec<&T>, Error> {
+        let Data::Raw(raw) = self else {
+            return Ok(Vec::new());
+        };
+        let size = std::mem::size_of::<T>();
+        let align = std::mem::align_of::<T>();
+        raw.iter()
+            .map(|bytes| {
+                if bytes.len() != size {
+                    return Err(Error::SizeMismatch {
+                        expected: size,
+                        found: bytes.len(),
+                    });
+                }
+                if bytes.as_ptr().align_offset(align) != 0 {
+                    return Err(Error::Unaligned(align));
+                }
+                // Safety: the element is aligned and sized for `T`, and `T: Pod` accepts any bit
+                // pattern.
+                Ok(unsafe { &*bytes.as_ptr().cast::<T>() })
+            })
+            .collect()
+    }
+
+    /// Return the name/value pairs of `Named` data, or `None` for any other variant.
+    pub fn named(&self) -> Option<&[Named<'a>]> {
+        match self {
+            Data::Named(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Return an iterator over the name/value pairs of `Named` data.
+    ///
+    /// The iterator is empty for any other variant.
+    pub fn as_named_iter(&self) -> impl Iterator<Item = &Named<'a>> {
+        self.named().unwrap_or_default().iter()
+    }
+
+    // Return the value of the first named statistic called `name`, if any.
+    pub(crate) fn named_value(&self, name: &str) -> Option<&NamedData<'a>> {
+        self.as_named_iter()
+            .find(|n| n.name == name)
+            .map(|n| &n.value)
+    }
+
+    // Return the value of the unsigned integer statistic `name`, or `Error::NotFound`.
+    pub(crate) fn named_u64(&self, name: &str) -> Result<u64, Error> {
+        self.named_value(name)
+            .and_then(NamedData::as_u64)
+            .ok_or(Error::NotFound)
+    }
+
+    // Return the value of the integer statistic `name`, or `Error::NotFound`.
+    pub(crate) fn named_i64(&self, name: &str) -> Result<i64, Error> {
+        self.named_value(name)
+            .and_then(NamedData::as_i64)
+            .ok_or(Error::NotFound)
+    }
+}
+
+/// A reusable buffer for reading kstat data with [`Ctl::read_into`].
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct DataBuf<'a> {
+    raw: Vec<&'a [u8]>,
+    named: Vec<Named<'a>>,
+    timer: Vec<Timer<'a>>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> DataBuf<'a> {
+    /// Create a new, empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Data from a single kstat, borrowed from a [`DataBuf`].
+///
+/// This is the same as [`Data`], but is returned by [`Ctl::read_into`].
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub enum DataRef<'a, 'b> {
+    Raw(&'b [&'a [u8]]),
+    Named(&'b [Named<'a>]),
+    Intr(Intr),
+    Io(Io),
+    Timer(&'b [Timer<'a>]),
+    Null,
+}
+
+#[cfg(feature = "std")]
+impl<'a, 'b> DataRef<'a, 'b> {
+    /// Copy this view into a [`Data`], which does not borrow the [`DataBuf`].
+    pub fn to_data(&self) -> Data<'a> {
+        match *self {
+            DataRef::Raw(r) => Data::Raw(r.to_vec()),
+            DataRef::Named(n) => Data::Named(n.to_vec()),
+            DataRef::Intr(i) => Data::Intr(i),
+            DataRef::Io(i) => Data::Io(i),
+            DataRef::Timer(t) => Data::Timer(t.to_vec()),
+            DataRef::Null => Data::Null,
+        }
+    }
+}
+
+/// An I/O kernel statistic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Io {
+    pub nread: u64,
+    pub nwritten: u64,
+    pub reads: u32,
+    pub writes: u32,
+    pub wtime: i64,
     pub wlentime: i64,
     pub wlastupdate: i64,
     pub rtime: i64,
@@ -379,6 +2470,62 @@ impl From<&sys::kstat_io_t> for Io {
     }
 }
 
+impl Io {
+    /// Return the difference between this and an earlier snapshot of the same statistic.
+    ///
+    /// The cumulative counters are subtracted, wrapping on overflow. The `wlastupdate` and
+    /// `rlastupdate` timestamps, and the instantaneous queue lengths `wcnt` and `rcnt`, are taken
+    /// from `self`.
+    pub fn delta(&self, earlier: &Io) -> Io {
+        Io {
+            nread: self.nread.wrapping_sub(earlier.nread),
+            nwritten: self.nwritten.wrapping_sub(earlier.nwritten),
+            reads: self.reads.wrapping_sub(earlier.reads),
+            writes: self.writes.wrapping_sub(earlier.writes),
+            wtime: self.wtime.wrapping_sub(earlier.wtime),
+            wlentime: self.wlentime.wrapping_sub(earlier.wlentime),
+            wlastupdate: self.wlastupdate,
+            rtime: self.rtime.wrapping_sub(earlier.rtime),
+            rlentime: self.rlentime.wrapping_sub(earlier.rlentime),
+            rlastupdate: self.rlastupdate,
+            wcnt: self.wcnt,
+            rcnt: self.rcnt,
+        }
+    }
+
+    /// Compute the I/O rates between an earlier snapshot and this one.
+    ///
+    /// `elapsed_ns` is the time between the two snapshots, in nanoseconds, usually the
+    /// difference in their `ks_snaptime`. All rates are zero if `elapsed_ns` is not positive.
+    pub fn throughput(&self, earlier: &Io, elapsed_ns: i64) -> IoRates {
+        if elapsed_ns <= 0 {
+            return IoRates::default();
+        }
+        let delta = self.delta(earlier);
+        let secs = elapsed_ns as f64 / 1e9;
+        IoRates {
+            read_bytes_per_sec: delta.nread as f64 / secs,
+            written_bytes_per_sec: delta.nwritten as f64 / secs,
+            reads_per_sec: f64::from(delta.reads) / secs,
+            writes_per_sec: f64::from(delta.writes) / secs,
+        }
+    }
+}
+
+/// Rates of I/O computed from two snapshots of an [`Io`] kernel statistic.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IoRates {
+    /// Bytes read per second.
+    pub read_bytes_per_sec: f64,
+    /// Bytes written per second.
+    pub written_bytes_per_sec: f64,
+    /// Read operations per second.
+    pub reads_per_sec: f64,
+    /// Write operations per second.
+    pub writes_per_sec: f64,
+}
+
 impl TryFrom<&*const sys::kstat_io_t> for Io {
     type Error = Error;
     fn try_from(k: &*const sys::kstat_io_t) -> Result<Self, Self::Error> {
@@ -391,7 +2538,8 @@ impl TryFrom<&*const sys::kstat_io_t> for Io {
 }
 
 /// A timer kernel statistic.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Timer<'a> {
     pub name: &'a str,
     pub num_events: usize,
@@ -402,11 +2550,39 @@ pub struct Timer<'a> {
     pub stop_time: i64,
 }
 
+impl<'a> Timer<'a> {
+    /// Return the total time elapsed over all events.
+    ///
+    /// As with the other durations of a timer, negative times are clamped to zero.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed_time.max(0) as u64)
+    }
+
+    /// Return the shortest time elapsed for a single event.
+    pub fn min(&self) -> Duration {
+        Duration::from_nanos(self.min_time.max(0) as u64)
+    }
+
+    /// Return the longest time elapsed for a single event.
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_time.max(0) as u64)
+    }
+
+    /// Return the average time elapsed per event, or zero if there have been no events.
+    pub fn average(&self) -> Duration {
+        if self.num_events == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos(self.elapsed_time.max(0) as u64 / self.num_events as u64)
+        }
+    }
+}
+
 impl<'a> TryFrom<&'a sys::kstat_timer_t> for Timer<'a> {
     type Error = Error;
     fn try_from(k: &'a sys::kstat_timer_t) -> Result<Self, Self::Error> {
         Ok(Self {
-            name: sys::array_to_cstr(&k.name)?,
+            name: sys::array_to_cstr(&k.name, StringField::TimerName)?,
             num_events: k.num_events as _,
             elapsed_time: k.elapsed_time,
             min_time: k.min_time,
@@ -429,7 +2605,8 @@ impl<'a> TryFrom<&'a *const sys::kstat_timer_t> for Timer<'a> {
 }
 
 /// Interrupt kernel statistic.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Intr {
     pub hard: u32,
     pub soft: u32,
@@ -450,6 +2627,47 @@ impl From<&sys::kstat_intr_t> for Intr {
     }
 }
 
+impl Intr {
+    /// Return the count of interrupts of the given kind.
+    pub fn get(&self, kind: IntrType) -> u32 {
+        match kind {
+            IntrType::Hard => self.hard,
+            IntrType::Soft => self.soft,
+            IntrType::Watchdog => self.watchdog,
+            IntrType::Spurious => self.spurious,
+            IntrType::Multisvc => self.multisvc,
+        }
+    }
+
+    /// Return an iterator over the count of each kind of interrupt.
+    pub fn iter(&self) -> impl Iterator<Item = (IntrType, u32)> + '_ {
+        IntrType::ALL
+            .iter()
+            .map(move |kind| (*kind, self.get(*kind)))
+    }
+}
+
+/// The kinds of interrupts counted in an interrupt kernel statistic.
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd, Hash)]
+pub enum IntrType {
+    Hard,
+    Soft,
+    Watchdog,
+    Spurious,
+    Multisvc,
+}
+
+impl IntrType {
+    /// All kinds of interrupts, in the order they appear in [`Intr`].
+    pub const ALL: [IntrType; 5] = [
+        IntrType::Hard,
+        IntrType::Soft,
+        IntrType::Watchdog,
+        IntrType::Spurious,
+        IntrType::Multisvc,
+    ];
+}
+
 impl TryFrom<&*const sys::kstat_intr_t> for Intr {
     type Error = Error;
     fn try_from(k: &*const sys::kstat_intr_t) -> Result<Self, Self::Error> {
@@ -462,7 +2680,8 @@ impl TryFrom<&*const sys::kstat_intr_t> for Intr {
 }
 
 /// A name/value data element from a named kernel statistic.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Named<'a> {
     pub name: &'a str,
     pub value: NamedData<'a>,
@@ -473,16 +2692,63 @@ impl<'a> Named<'a> {
     pub fn data_type(&self) -> NamedType {
         self.value.data_type()
     }
+
+    /// Guess the unit of the statistic from its name, using the default [`UnitRules`].
+    #[cfg(feature = "std")]
+    pub fn infer_unit(&self) -> Option<Unit> {
+        self.infer_unit_with(UnitRules::default_ref())
+    }
+
+    /// Guess the unit of the statistic from its name, using the given `rules`.
+    #[cfg(feature = "std")]
+    pub fn infer_unit_with(&self, rules: &UnitRules) -> Option<Unit> {
+        rules.infer(self.name)
+    }
+}
+
+impl<'a> fmt::Display for Named<'a> {
+    /// Format the name/value pair tab-separated, as `kstat -p` does.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}", self.name, self.value)
+    }
+}
+
+/// Index named statistics by their names, for looking up many of them.
+///
+/// If several statistics share a name, the first of them is kept, which matches looking them
+/// up one at a time with `Iterator::find`.
+#[cfg(feature = "std")]
+pub fn index_by_name<'a>(stats: &'a [Named<'a>]) -> HashMap<&'a str, &'a NamedData<'a>> {
+    let mut index = HashMap::with_capacity(stats.len());
+    for stat in stats.iter() {
+        index.entry(stat.name).or_insert(&stat.value);
+    }
+    index
 }
 
 /// The value part of a name-value kernel statistic.
-#[derive(Clone, Debug)]
+///
+/// The `Char` and `String` variants borrow from the data buffer of the [`Kstat`] they were read
+/// from. That buffer is owned by the [`Ctl`], and is reused by the next read of the same kstat and
+/// freed when the chain changes in [`Ctl::update`] or when the `Ctl` is dropped. The borrow only
+/// prevents the latter, so these values should not be held across a subsequent read of the same
+/// kstat. Use [`NamedData::into_owned_string`] or [`OwnedNamedData`] to keep a copy.
+///
+/// When serialized, this is tagged with its type, e.g. `{"type":"uint64","value":42}`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "lowercase")
+)]
 pub enum NamedData<'a> {
     Char(&'a [u8]),
     Int32(i32),
     UInt32(u32),
     Int64(i64),
     UInt64(u64),
+    Float(f32),
+    Double(f64),
     String(&'a str),
 }
 
@@ -495,21 +2761,135 @@ impl<'a> NamedData<'a> {
             NamedData::UInt32(_) => NamedType::UInt32,
             NamedData::Int64(_) => NamedType::Int64,
             NamedData::UInt64(_) => NamedType::UInt64,
+            NamedData::Float(_) => NamedType::Float,
+            NamedData::Double(_) => NamedType::Double,
             NamedData::String(_) => NamedType::String,
         }
     }
+
+    /// Return the value of a `Char` statistic as a string.
+    ///
+    /// The data is truncated at the first NUL byte, if any, and decoded as UTF-8. This returns
+    /// `None` for any other variant.
+    pub fn as_char_str(&self) -> Option<Result<&'a str, Error>> {
+        match self {
+            NamedData::Char(slice) => {
+                let len = slice.iter().position(|b| *b == 0).unwrap_or(slice.len());
+                Some(
+                    core::str::from_utf8(&slice[..len]).map_err(|_| Error::InvalidString {
+                        field: StringField::StatValue,
+                    }),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// Copy the value of a `String` statistic, so that it may outlive the kstat's data buffer.
+    ///
+    /// This returns `None` for any other variant.
+    pub fn into_owned_string(self) -> Option<String> {
+        match self {
+            NamedData::String(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Return the value as a `u64`, if it is an integer that fits.
+    ///
+    /// This returns `None` for non-integer data, and for negative signed values.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            NamedData::Int32(i) => u64::try_from(*i).ok(),
+            NamedData::UInt32(u) => Some(u64::from(*u)),
+            NamedData::Int64(i) => u64::try_from(*i).ok(),
+            NamedData::UInt64(u) => Some(*u),
+            NamedData::Float(_) | NamedData::Double(_) => None,
+            NamedData::Char(_) | NamedData::String(_) => None,
+        }
+    }
+
+    /// Return the value as an `i64`, if it is an integer that fits.
+    ///
+    /// This returns `None` for non-integer data, and for unsigned values larger than
+    /// `i64::MAX`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            NamedData::Int32(i) => Some(i64::from(*i)),
+            NamedData::UInt32(u) => Some(i64::from(*u)),
+            NamedData::Int64(i) => Some(*i),
+            NamedData::UInt64(u) => i64::try_from(*u).ok(),
+            NamedData::Float(_) | NamedData::Double(_) => None,
+            NamedData::Char(_) | NamedData::String(_) => None,
+        }
+    }
+
+    /// Return the value as an `f64`, if it is numeric.
+    ///
+    /// Note that 64-bit values may lose precision in the conversion.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            NamedData::Int32(i) => Some(f64::from(*i)),
+            NamedData::UInt32(u) => Some(f64::from(*u)),
+            NamedData::Int64(i) => Some(*i as f64),
+            NamedData::UInt64(u) => Some(*u as f64),
+            NamedData::Float(x) => Some(f64::from(*x)),
+            NamedData::Double(x) => Some(*x),
+            NamedData::Char(_) | NamedData::String(_) => None,
+        }
+    }
+
+    /// Return the change in a counter since an `earlier` value of it.
+    ///
+    /// Kernel counters are fixed-width and wrap around, so the difference is computed with
+    /// wrapping arithmetic at the width of the counter. Signed counters are treated as unsigned
+    /// values of the same width. This returns `None` if the two values are of different types,
+    /// or are not integers.
+    pub fn counter_delta(&self, earlier: &NamedData<'_>) -> Option<u64> {
+        match (self, earlier) {
+            (NamedData::Int32(new), NamedData::Int32(old)) => {
+                Some(u64::from((*new as u32).wrapping_sub(*old as u32)))
+            }
+            (NamedData::UInt32(new), NamedData::UInt32(old)) => {
+                Some(u64::from(new.wrapping_sub(*old)))
+            }
+            (NamedData::Int64(new), NamedData::Int64(old)) => {
+                Some((*new as u64).wrapping_sub(*old as u64))
+            }
+            (NamedData::UInt64(new), NamedData::UInt64(old)) => Some(new.wrapping_sub(*old)),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> fmt::Display for NamedData<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamedData::Char(slice) => {
+                let len = slice.iter().position(|b| *b == 0).unwrap_or(slice.len());
+                write!(f, "{}", String::from_utf8_lossy(&slice[..len]))
+            }
+            NamedData::Int32(i) => write!(f, "{}", i),
+            NamedData::UInt32(u) => write!(f, "{}", u),
+            NamedData::Int64(i) => write!(f, "{}", i),
+            NamedData::UInt64(u) => write!(f, "{}", u),
+            NamedData::Float(x) => write!(f, "{}", x),
+            NamedData::Double(x) => write!(f, "{}", x),
+            NamedData::String(s) => write!(f, "{}", s),
+        }
+    }
 }
 
 impl<'a> TryFrom<&'a sys::kstat_named_t> for Named<'a> {
     type Error = Error;
     fn try_from(k: &'a sys::kstat_named_t) -> Result<Self, Self::Error> {
-        let name = sys::array_to_cstr(&k.name)?;
+        let name = sys::array_to_cstr(&k.name, StringField::StatName)?;
         match NamedType::try_from(k.data_type)? {
             NamedType::Char => {
                 let slice = unsafe {
-                    let p = k.value.charc.as_ptr() as *const u8;
+                    let p = k.value.charc.as_ptr();
                     let len = k.value.charc.len();
-                    std::slice::from_raw_parts(p, len)
+                    core::slice::from_raw_parts(p, len)
                 };
                 Ok(Named {
                     name,
@@ -533,6 +2913,32 @@ impl<'a> TryFrom<&'a sys::kstat_named_t> for Named<'a> {
                 name,
                 value: NamedData::UInt64(unsafe { k.value.ui64 }),
             }),
+            NamedType::Float => Ok(Named {
+                name,
+                value: NamedData::Float(unsafe { k.value.f }),
+            }),
+            NamedType::Double => Ok(Named {
+                name,
+                value: NamedData::Double(unsafe { k.value.d }),
+            }),
+            // A `long` is only 32 bits wide on ILP32 targets, so these conversions are not
+            // always to the same type.
+            NamedType::Long => {
+                #[allow(clippy::useless_conversion)]
+                let value = i64::from(unsafe { k.value.l });
+                Ok(Named {
+                    name,
+                    value: NamedData::Int64(value),
+                })
+            }
+            NamedType::ULong => {
+                #[allow(clippy::useless_conversion)]
+                let value = u64::from(unsafe { k.value.ul });
+                Ok(Named {
+                    name,
+                    value: NamedData::UInt64(value),
+                })
+            }
             NamedType::String => {
                 let s = (&unsafe { k.value.str }).try_into()?;
                 Ok(Named {
@@ -544,9 +2950,140 @@ impl<'a> TryFrom<&'a sys::kstat_named_t> for Named<'a> {
     }
 }
 
-#[cfg(test)]
+/// Owned data from a single kstat.
+///
+/// This is the same as [`Data`], but does not borrow from the [`Ctl`] used to read it. This
+/// allows it to be stored across updates of the kstat chain, or subsequent reads of the kstat.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum OwnedData {
+    Raw(Vec<Vec<u8>>),
+    Named(Vec<OwnedNamed>),
+    Intr(Intr),
+    Io(Io),
+    Timer(Vec<OwnedTimer>),
+    Null,
+}
+
+impl<'a> From<&Data<'a>> for OwnedData {
+    fn from(d: &Data<'a>) -> Self {
+        match d {
+            Data::Raw(r) => OwnedData::Raw(r.iter().map(|r| r.to_vec()).collect()),
+            Data::Named(n) => OwnedData::Named(n.iter().map(OwnedNamed::from).collect()),
+            Data::Intr(i) => OwnedData::Intr(*i),
+            Data::Io(i) => OwnedData::Io(*i),
+            Data::Timer(t) => OwnedData::Timer(t.iter().map(OwnedTimer::from).collect()),
+            Data::Null => OwnedData::Null,
+        }
+    }
+}
+
+/// An owned timer kernel statistic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OwnedTimer {
+    pub name: String,
+    pub num_events: usize,
+    pub elapsed_time: i64,
+    pub min_time: i64,
+    pub max_time: i64,
+    pub start_time: i64,
+    pub stop_time: i64,
+}
+
+impl<'a> From<&Timer<'a>> for OwnedTimer {
+    fn from(t: &Timer<'a>) -> Self {
+        Self {
+            name: t.name.to_string(),
+            num_events: t.num_events,
+            elapsed_time: t.elapsed_time,
+            min_time: t.min_time,
+            max_time: t.max_time,
+            start_time: t.start_time,
+            stop_time: t.stop_time,
+        }
+    }
+}
+
+/// An owned name/value data element from a named kernel statistic.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OwnedNamed {
+    pub name: String,
+    pub value: OwnedNamedData,
+}
+
+impl OwnedNamed {
+    /// Return the data type of a named kernel statistic.
+    pub fn data_type(&self) -> NamedType {
+        self.value.data_type()
+    }
+}
+
+impl<'a> From<&Named<'a>> for OwnedNamed {
+    fn from(n: &Named<'a>) -> Self {
+        Self {
+            name: n.name.to_string(),
+            value: OwnedNamedData::from(&n.value),
+        }
+    }
+}
+
+/// The owned value part of a name-value kernel statistic.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "lowercase")
+)]
+pub enum OwnedNamedData {
+    Char(Vec<u8>),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Float(f32),
+    Double(f64),
+    String(String),
+}
+
+impl OwnedNamedData {
+    /// Return the data type of a named kernel statistic.
+    pub fn data_type(&self) -> NamedType {
+        match self {
+            OwnedNamedData::Char(_) => NamedType::Char,
+            OwnedNamedData::Int32(_) => NamedType::Int32,
+            OwnedNamedData::UInt32(_) => NamedType::UInt32,
+            OwnedNamedData::Int64(_) => NamedType::Int64,
+            OwnedNamedData::UInt64(_) => NamedType::UInt64,
+            OwnedNamedData::Float(_) => NamedType::Float,
+            OwnedNamedData::Double(_) => NamedType::Double,
+            OwnedNamedData::String(_) => NamedType::String,
+        }
+    }
+}
+
+impl<'a> From<&NamedData<'a>> for OwnedNamedData {
+    fn from(n: &NamedData<'a>) -> Self {
+        match n {
+            NamedData::Char(c) => OwnedNamedData::Char(c.to_vec()),
+            NamedData::Int32(i) => OwnedNamedData::Int32(*i),
+            NamedData::UInt32(u) => OwnedNamedData::UInt32(*u),
+            NamedData::Int64(i) => OwnedNamedData::Int64(*i),
+            NamedData::UInt64(u) => OwnedNamedData::UInt64(*u),
+            NamedData::Float(f) => OwnedNamedData::Float(*f),
+            NamedData::Double(d) => OwnedNamedData::Double(*d),
+            NamedData::String(s) => OwnedNamedData::String(s.to_string()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
+    use crate::fake::FakeKstat;
+    #[cfg(not(feature = "stubs"))]
     use std::collections::BTreeMap;
 
     #[test]
@@ -562,11 +3099,18 @@ mod test {
         }
     }
 
+    // This predates the lints below, and is kept as written.
+    #[allow(
+        clippy::double_ended_iterator_last,
+        clippy::expect_fun_call,
+        clippy::redundant_static_lifetimes
+    )]
+    #[cfg(not(feature = "stubs"))]
     #[test]
     fn compare_with_kstat_cli() {
         let ctl = Ctl::new().expect("Failed to create kstat control");
         let mut kstat = ctl
-            .filter(Some("cpu_info"), Some(0), Some("cpu_info0"))
+            .filter(Some("cpu_info"), Instance::Num(0), Some("cpu_info0"))
             .next()
             .expect("Failed to find kstat cpu_info:0:cpu_info0");
         if let Data::Named(data) = ctl.read(&mut kstat).expect("Failed to read kstat") {
@@ -648,13 +3192,1905 @@ mod test {
                             assert_eq!(sl, by, "Expected equal bytes, found {} and {}", sl, by);
                         }
                     }
-                    NamedData::Int32(i) => assert_eq!(i, value.parse().unwrap()),
-                    NamedData::UInt32(u) => assert_eq!(u, value.parse().unwrap()),
-                    NamedData::Int64(i) => assert_eq!(i, value.parse().unwrap()),
-                    NamedData::UInt64(u) => assert_eq!(u, value.parse().unwrap()),
+                    NamedData::Int32(i) => assert_eq!(i, value.parse::<i32>().unwrap()),
+                    NamedData::UInt32(u) => assert_eq!(u, value.parse::<u32>().unwrap()),
+                    NamedData::Int64(i) => assert_eq!(i, value.parse::<i64>().unwrap()),
+                    NamedData::UInt64(u) => assert_eq!(u, value.parse::<u64>().unwrap()),
+                    NamedData::Float(f) => assert_eq!(f, value.parse::<f32>().unwrap()),
+                    NamedData::Double(d) => assert_eq!(d, value.parse::<f64>().unwrap()),
                     NamedData::String(s) => assert_eq!(s, value),
                 }
             }
         }
     }
+
+    #[test]
+    fn filter_requires_all_fields_to_match() {
+        let mut chain = [
+            FakeKstat::new("cpu_info", 0, "cpu_info0", Type::Named),
+            FakeKstat::new("cpu_info", 1, "cpu_info1", Type::Named),
+            FakeKstat::new("cpu_info", 0, "cpu_info_alias", Type::Named),
+            FakeKstat::new("zfs", 0, "arcstats", Type::Named),
+            FakeKstat::new("sd", 1, "sd1", Type::Io),
+        ];
+        let ctl = fake_ctl(&mut chain);
+        let ids = |module, instance, name| {
+            ctl.filter(module, instance, name)
+                .map(|kstat| kstat.id())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(Some("cpu_info"), Instance::Num(0), Some("cpu_info0")),
+            [KstatId::new("cpu_info", 0, "cpu_info0")]
+        );
+        assert!(ids(Some("cpu_info"), Instance::Num(1), Some("cpu_info0")).is_empty());
+        assert!(ids(Some("zfs"), Instance::Num(0), Some("cpu_info0")).is_empty());
+        assert_eq!(
+            ids(None, Instance::Num(0), None),
+            [
+                KstatId::new("cpu_info", 0, "cpu_info0"),
+                KstatId::new("cpu_info", 0, "cpu_info_alias"),
+                KstatId::new("zfs", 0, "arcstats"),
+            ]
+        );
+        assert_eq!(
+            ids(Some("cpu_info"), Instance::Any, Some("cpu_info1")),
+            [KstatId::new("cpu_info", 1, "cpu_info1")]
+        );
+        assert_eq!(ids(None, Instance::Any, None).len(), 5);
+    }
+
+    fn fake_kstat(
+        class: &'static str,
+        module: &'static str,
+        instance: i32,
+        name: &'static str,
+        crtime: i64,
+    ) -> Kstat<'static> {
+        Kstat {
+            ks_crtime: crtime,
+            ks_snaptime: 0,
+            ks_module: module,
+            ks_instance: instance,
+            ks_name: name,
+            ks_type: Type::Named,
+            ks_class: class,
+            ks_flags: KstatFlags::default(),
+            ks: std::ptr::null_mut(),
+            ctl: std::ptr::null_mut(),
+            read: false,
+        }
+    }
+
+    #[test]
+    fn kstat_equality_ignores_snapshot() {
+        let a = fake_kstat("misc", "cpu_info", 0, "cpu_info0", 10);
+        let mut b = a;
+        b.ks_snaptime = 20;
+        b.read = true;
+        assert_eq!(a, b);
+        assert_ne!(a, fake_kstat("misc", "cpu_info", 0, "cpu_info0", 20));
+    }
+
+    #[test]
+    fn kstat_ordering_is_total() {
+        let kstats = vec![
+            fake_kstat("misc", "zfs", 0, "arcstats", 10),
+            fake_kstat("misc", "cpu_info", 1, "cpu_info1", 10),
+            fake_kstat("misc", "cpu_info", 0, "cpu_info0", 20),
+            fake_kstat("misc", "cpu_info", 0, "cpu_info0", 10),
+            fake_kstat("disk", "sd", 0, "sd0", 10),
+            fake_kstat("net", "link", 0, "net0", 10),
+            fake_kstat("misc", "cpu_info", 0, "cpu", 10),
+        ];
+        for a in kstats.iter() {
+            assert_eq!(a.cmp(a), Ordering::Equal);
+            for b in kstats.iter() {
+                assert_eq!(
+                    a.cmp(b),
+                    b.cmp(a).reverse(),
+                    "Ordering is not antisymmetric"
+                );
+                assert_eq!(a == b, a.cmp(b) == Ordering::Equal);
+                for c in kstats.iter() {
+                    if a <= b && b <= c {
+                        assert!(a <= c, "Ordering is not transitive");
+                    }
+                }
+            }
+        }
+
+        let mut sorted = kstats.clone();
+        sorted.sort();
+        let names = sorted
+            .iter()
+            .map(|ks| {
+                (
+                    ks.ks_class,
+                    ks.ks_module,
+                    ks.ks_instance,
+                    ks.ks_name,
+                    ks.ks_crtime,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                ("disk", "sd", 0, "sd0", 10),
+                ("misc", "cpu_info", 0, "cpu", 10),
+                ("misc", "cpu_info", 0, "cpu_info0", 10),
+                ("misc", "cpu_info", 0, "cpu_info0", 20),
+                ("misc", "cpu_info", 1, "cpu_info1", 10),
+                ("misc", "zfs", 0, "arcstats", 10),
+                ("net", "link", 0, "net0", 10),
+            ]
+        );
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn lookup_matches_filter() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let kstat = ctl
+            .lookup(Some("cpu_info"), Instance::Num(0), Some("cpu_info0"))
+            .expect("Failed to lookup kstat")
+            .expect("Failed to find kstat cpu_info:0:cpu_info0");
+        let expected = ctl
+            .filter(Some("cpu_info"), Instance::Num(0), Some("cpu_info0"))
+            .next()
+            .unwrap();
+        assert_eq!(kstat, expected);
+    }
+
+    #[test]
+    fn lookup_returns_none_when_missing() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        assert!(ctl
+            .lookup(Some("no-such-module"), Instance::Any, Some("no-such-name"))
+            .expect("Failed to lookup kstat")
+            .is_none());
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn update_mut_reports_chain_changes() {
+        let mut ctl = Ctl::new().expect("Failed to create kstat control");
+        let before = unsafe { (*ctl.ctl).kc_chain_id };
+        let changed = ctl.update_mut().expect("Failed to update kstat control");
+        let after = unsafe { (*ctl.ctl).kc_chain_id };
+        assert_eq!(changed, before != after);
+        assert!(ctl.iter().next().is_some());
+    }
+
+    #[test]
+    fn update_changed_reports_chain_changes() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let before = unsafe { (*ctl.ctl).kc_chain_id };
+        let (ctl, changed) = ctl
+            .update_changed()
+            .expect("Failed to update kstat control");
+        let after = unsafe { (*ctl.ctl).kc_chain_id };
+        assert_eq!(changed, before != after);
+    }
+
+    #[test]
+    fn named_data_numeric_coercion() {
+        assert_eq!(
+            NamedData::UInt32(u32::MAX).as_u64(),
+            Some(u64::from(u32::MAX))
+        );
+        assert_eq!(NamedData::UInt64(u64::MAX).as_u64(), Some(u64::MAX));
+        assert_eq!(NamedData::Int32(7).as_u64(), Some(7));
+        assert_eq!(NamedData::Int32(-1).as_u64(), None);
+        assert_eq!(NamedData::Int64(-1).as_u64(), None);
+
+        assert_eq!(NamedData::Int32(-1).as_i64(), Some(-1));
+        assert_eq!(
+            NamedData::UInt32(u32::MAX).as_i64(),
+            Some(i64::from(u32::MAX))
+        );
+        assert_eq!(NamedData::UInt64(u64::MAX).as_i64(), None);
+
+        assert_eq!(NamedData::Int64(-2).as_f64(), Some(-2.0));
+        assert_eq!(NamedData::UInt64(42).as_f64(), Some(42.0));
+
+        for data in [NamedData::Char(b"abc"), NamedData::String("abc")] {
+            assert_eq!(data.as_u64(), None);
+            assert_eq!(data.as_i64(), None);
+            assert_eq!(data.as_f64(), None);
+        }
+    }
+
+    #[test]
+    fn named_display() {
+        assert_eq!(NamedData::Int32(-3).to_string(), "-3");
+        assert_eq!(NamedData::UInt32(3).to_string(), "3");
+        assert_eq!(NamedData::Int64(-4).to_string(), "-4");
+        assert_eq!(NamedData::UInt64(4).to_string(), "4");
+        assert_eq!(NamedData::String("on-line").to_string(), "on-line");
+        assert_eq!(NamedData::Char(b"i386\0\0\0\0").to_string(), "i386");
+        assert_eq!(NamedData::Char(b"full").to_string(), "full");
+        let named = Named {
+            name: "clock_MHz",
+            value: NamedData::Int64(2400),
+        };
+        assert_eq!(named.to_string(), "clock_MHz\t2400");
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn read_by_name() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let data = ctl
+            .read_by_name("cpu_info", 0, "cpu_info0")
+            .expect("Failed to read cpu_info:0:cpu_info0");
+        assert!(matches!(data, Data::Named(_)));
+        assert!(matches!(
+            ctl.read_by_name("no-such-module", 0, "no-such-name"),
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[cfg(feature = "stubs")]
+    #[test]
+    fn read_retry_updates_after_transient_failure() {
+        let id = KstatId::new("zfs", 0, "arcstats");
+        let chain = crate::stub::set_chain(vec![crate::fake::arcstats(1, 1024)]);
+        let mut ctl = Ctl::new().expect("Failed to create stub kstat control");
+
+        // Replace the kstat behind the handle's back, so its next read fails once.
+        chain.set(vec![crate::fake::arcstats(2, 2048)]);
+        assert!(matches!(ctl.read_retry(&id, 0), Err(Error::Gone)));
+        match ctl
+            .read_retry(&id, 1)
+            .expect("Failed to read after retrying")
+        {
+            Data::Named(named) => assert_eq!(named[0].value, NamedData::UInt64(2048)),
+            other => panic!("expected named data, found {:?}", other),
+        }
+
+        chain.set(Vec::new());
+        assert!(matches!(ctl.read_retry(&id, 1), Err(Error::NotFound)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_named_data() {
+        let data = Data::Named(vec![
+            Named {
+                name: "ncpus",
+                value: NamedData::UInt64(42),
+            },
+            Named {
+                name: "vendor",
+                value: NamedData::String("AuthenticAMD"),
+            },
+            Named {
+                name: "arch",
+                value: NamedData::Char(b"i386"),
+            },
+        ]);
+        let json = serde_json::to_value(&data).expect("Failed to serialize data");
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "named": [
+                    { "name": "ncpus", "value": { "type": "uint64", "value": 42 } },
+                    { "name": "vendor", "value": { "type": "string", "value": "AuthenticAMD" } },
+                    { "name": "arch", "value": { "type": "char", "value": [105, 51, 56, 54] } },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn data_to_owned() {
+        let data = Data::Named(vec![
+            Named {
+                name: "ncpus",
+                value: NamedData::UInt64(42),
+            },
+            Named {
+                name: "vendor",
+                value: NamedData::String("AuthenticAMD"),
+            },
+            Named {
+                name: "arch",
+                value: NamedData::Char(b"i386"),
+            },
+            Named {
+                name: "offset",
+                value: NamedData::Int32(-1),
+            },
+        ]);
+        let Data::Named(named) = &data else {
+            unreachable!();
+        };
+        let OwnedData::Named(owned) = data.to_owned() else {
+            panic!("Expected owned named data");
+        };
+        assert_eq!(named.len(), owned.len());
+        for (n, o) in named.iter().zip(owned.iter()) {
+            assert_eq!(n.name, o.name);
+            assert_eq!(n.data_type(), o.data_type());
+            match (&n.value, &o.value) {
+                (NamedData::Char(a), OwnedNamedData::Char(b)) => assert_eq!(a, b),
+                (NamedData::Int32(a), OwnedNamedData::Int32(b)) => assert_eq!(a, b),
+                (NamedData::UInt32(a), OwnedNamedData::UInt32(b)) => assert_eq!(a, b),
+                (NamedData::Int64(a), OwnedNamedData::Int64(b)) => assert_eq!(a, b),
+                (NamedData::UInt64(a), OwnedNamedData::UInt64(b)) => assert_eq!(a, b),
+                (NamedData::String(a), OwnedNamedData::String(b)) => assert_eq!(a, b),
+                (a, b) => panic!("Mismatched values: {:?} and {:?}", a, b),
+            }
+        }
+    }
+
+    #[test]
+    fn data_requires_read_when_data_is_null() {
+        let fake = FakeKstat::new("unix", 0, "empty", Type::Raw);
+        let mut kstat = Kstat::try_from(&fake.ks).expect("Failed to build kstat");
+        assert!(!kstat.is_read());
+        assert!(matches!(kstat.data(), Err(Error::NullData)));
+
+        // Named data with no entries and a null data pointer is valid once read.
+        kstat.ks_type = Type::Named;
+        kstat.read = true;
+        assert!(matches!(kstat.data(), Ok(Data::Named(v)) if v.is_empty()));
+    }
+
+    #[test]
+    fn named_data_char_str() {
+        let terminated = b"i386\0\0\0\0\0\0\0\0\0\0\0\0";
+        assert_eq!(
+            NamedData::Char(terminated).as_char_str().unwrap().unwrap(),
+            "i386"
+        );
+        let full = b"0123456789abcdef";
+        assert_eq!(
+            NamedData::Char(full).as_char_str().unwrap().unwrap(),
+            "0123456789abcdef"
+        );
+        let invalid = b"\xff\xfe\0";
+        assert!(matches!(
+            NamedData::Char(invalid).as_char_str(),
+            Some(Err(Error::InvalidString {
+                field: StringField::StatValue
+            }))
+        ));
+        assert!(NamedData::String("i386").as_char_str().is_none());
+        assert!(NamedData::UInt64(0).as_char_str().is_none());
+    }
+
+    #[test]
+    fn intr_iter() {
+        let intr = Intr {
+            hard: 1,
+            soft: 2,
+            watchdog: 3,
+            spurious: 4,
+            multisvc: 5,
+        };
+        assert_eq!(intr.get(IntrType::Watchdog), 3);
+        let kinds = intr.iter().map(|(kind, _)| kind).collect::<Vec<_>>();
+        assert_eq!(kinds, IntrType::ALL);
+        assert_eq!(intr.iter().map(|(_, count)| count).sum::<u32>(), 15);
+    }
+
+    #[test]
+    fn io_delta_and_throughput() {
+        let earlier = Io {
+            nread: 1000,
+            nwritten: 2000,
+            reads: 10,
+            writes: u32::MAX,
+            wtime: 100,
+            wlentime: 200,
+            wlastupdate: 300,
+            rtime: 400,
+            rlentime: 500,
+            rlastupdate: 600,
+            wcnt: 1,
+            rcnt: 2,
+        };
+        let later = Io {
+            nread: 5000,
+            nwritten: 4000,
+            reads: 30,
+            writes: 9,
+            wtime: 150,
+            wlentime: 250,
+            wlastupdate: 1300,
+            rtime: 450,
+            rlentime: 550,
+            rlastupdate: 1600,
+            wcnt: 3,
+            rcnt: 4,
+        };
+        let delta = later.delta(&earlier);
+        assert_eq!(delta.nread, 4000);
+        assert_eq!(delta.nwritten, 2000);
+        assert_eq!(delta.reads, 20);
+        assert_eq!(delta.writes, 10);
+        assert_eq!(delta.wtime, 50);
+        assert_eq!(delta.wlastupdate, 1300);
+        assert_eq!(delta.rlastupdate, 1600);
+        assert_eq!(delta.wcnt, 3);
+        assert_eq!(delta.rcnt, 4);
+
+        // Two seconds elapsed.
+        let rates = later.throughput(&earlier, 2_000_000_000);
+        assert_eq!(
+            rates,
+            IoRates {
+                read_bytes_per_sec: 2000.0,
+                written_bytes_per_sec: 1000.0,
+                reads_per_sec: 10.0,
+                writes_per_sec: 5.0,
+            }
+        );
+        assert_eq!(later.throughput(&earlier, 0), IoRates::default());
+    }
+
+    #[test]
+    fn iter_raw_includes_every_kstat() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let raw = ctl.iter_raw().collect::<Vec<_>>();
+        let ok = raw.into_iter().filter_map(Result::ok).collect::<Vec<_>>();
+        assert_eq!(ok, ctl.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn lossy_kstat_from_invalid_names() {
+        let mut fake = FakeKstat::new("bad?mod", 0, "name", Type::Named);
+        fake.ks.ks_module[3] = 0xff_u8 as _;
+        assert!(matches!(
+            Kstat::try_from(&fake.ks),
+            Err(Error::InvalidString { .. })
+        ));
+        let lossy = KstatLossy::try_from(&fake.ks).expect("Failed to build lossy kstat");
+        assert_eq!(lossy.ks_module, "bad\u{FFFD}mod");
+        assert_eq!(lossy.ks_name, "name");
+        assert_eq!(lossy.ks_type, Type::Named);
+    }
+
+    #[test]
+    fn iter_lossy_includes_every_kstat() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        assert!(ctl.iter_lossy().count() >= ctl.iter().count());
+    }
+
+    #[test]
+    fn kstat_flags() {
+        let fake = FakeKstat::new("unix", 0, "flags", Type::Raw)
+            .flags(KstatFlags::WRITABLE | KstatFlags::VIRTUAL);
+        let kstat = fake.kstat();
+        assert!(kstat.ks_flags.is_writable());
+        assert!(kstat.ks_flags.is_virtual());
+        assert!(!kstat.ks_flags.is_persistent());
+        assert!(kstat
+            .ks_flags
+            .contains(KstatFlags::WRITABLE | KstatFlags::VIRTUAL));
+        assert_eq!(kstat.ks_flags.bits(), 0x05);
+    }
+
+    #[cfg(feature = "write")]
+    #[test]
+    fn write_rejects_unwritable_kstats() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let fake = FakeKstat::new("unix", 0, "readonly", Type::Raw);
+        let mut kstat = fake.kstat();
+        assert!(matches!(
+            ctl.write(&mut kstat, &Data::Null),
+            Err(Error::NotWritable)
+        ));
+    }
+
+    #[cfg(feature = "write")]
+    #[test]
+    fn write_rejects_mismatched_data() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let fake = FakeKstat::new("sd", 0, "sd0", Type::Io)
+            .flags(KstatFlags::WRITABLE)
+            .io(Io::default());
+        let mut kstat = fake.kstat();
+        let named = Data::Named(vec![Named {
+            name: "foo",
+            value: NamedData::UInt64(0),
+        }]);
+        assert!(matches!(
+            ctl.write(&mut kstat, &named),
+            Err(Error::DataMismatch)
+        ));
+    }
+
+    #[cfg(feature = "write")]
+    #[test]
+    fn reset_rejects_unwritable_kstats() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let fake = FakeKstat::new("unix", 0, "readonly", Type::Raw);
+        let kstat = fake.kstat();
+        assert!(!kstat.is_writable());
+        assert!(matches!(kstat.reset(&ctl), Err(Error::NotWritable)));
+    }
+
+    #[cfg(all(feature = "write", feature = "stubs"))]
+    #[test]
+    fn reset_zeroes_writable_kstat() {
+        let _chain =
+            crate::stub::set_chain(vec![FakeKstat::new("unix", 0, "counters", Type::Named)
+                .flags(KstatFlags::WRITABLE)
+                .named("reads", NamedData::UInt64(7))
+                .named("errors", NamedData::Int32(-2))
+                .named("label", NamedData::Char(b"disk"))]);
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let kstat = ctl
+            .lookup(Some("unix"), Instance::Any, Some("counters"))
+            .expect("Failed to look up kstat")
+            .expect("Expected the counters kstat");
+        kstat.reset(&ctl).expect("Failed to reset kstat");
+        let Data::Named(named) = ctl.read(&mut kstat.clone()).expect("Failed to read kstat") else {
+            panic!("Expected named data");
+        };
+        assert_eq!(named[0].value, NamedData::UInt64(0));
+        assert_eq!(named[1].value, NamedData::Int32(0));
+        assert_eq!(named[2].value, NamedData::Char(&[0; NAMED_CHAR_LEN]));
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn age_of_fresh_kstat() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let mut kstat = ctl
+            .lookup(Some("cpu_info"), Instance::Num(0), Some("cpu_info0"))
+            .expect("Failed to lookup kstat")
+            .expect("Failed to find kstat cpu_info:0:cpu_info0");
+        ctl.read(&mut kstat).expect("Failed to read kstat");
+        assert!(kstat.age_ns() >= 0);
+    }
+
+    #[test]
+    fn iter_by_type() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let io = ctl.iter_by_type(Type::Io).collect::<Vec<_>>();
+        assert!(io.iter().all(|kstat| kstat.ks_type == Type::Io));
+        assert_eq!(io, ctl.iter().of_type(Type::Io).collect::<Vec<_>>());
+        for mut kstat in io.into_iter() {
+            assert!(matches!(ctl.read(&mut kstat), Ok(Data::Io(_))));
+        }
+    }
+
+    #[test]
+    fn kstat_id() {
+        let kstat = fake_kstat("misc", "cpu_info", 0, "cpu_info0", 0);
+        let id = kstat.id();
+        assert_eq!(id, KstatId::new("cpu_info", 0, "cpu_info0"));
+        assert_eq!(id.to_string(), "cpu_info:0:cpu_info0");
+
+        let mut ids = vec![
+            KstatId::new("cpu_info", 1, "cpu_info1"),
+            KstatId::new("cpu", 0, "sys"),
+            id.clone(),
+            id.clone(),
+        ];
+        ids.sort();
+        ids.dedup();
+        assert_eq!(
+            ids,
+            vec![
+                KstatId::new("cpu", 0, "sys"),
+                KstatId::new("cpu_info", 0, "cpu_info0"),
+                KstatId::new("cpu_info", 1, "cpu_info1"),
+            ]
+        );
+        let set = ids.into_iter().collect::<std::collections::HashSet<_>>();
+        assert!(set.contains(&id));
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn select_matches_filter() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let sel: Selector = "cpu_info:0::".parse().unwrap();
+        let selected = ctl.select(&sel).collect::<Vec<_>>();
+        assert!(!selected.is_empty());
+        assert_eq!(
+            selected,
+            ctl.filter(Some("cpu_info"), Instance::Num(0), None)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn filter_glob() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let cpus = ctl
+            .filter_glob(Some("cpu_*"), Some("0"), Some("cpu_info?"))
+            .expect("Failed to compile patterns")
+            .collect::<Vec<_>>();
+        assert_eq!(
+            cpus,
+            ctl.filter(Some("cpu_info"), Instance::Num(0), Some("cpu_info0"))
+                .collect::<Vec<_>>()
+        );
+        assert!(ctl
+            .filter_glob(Some("cpu_*"), None, None)
+            .unwrap()
+            .all(|kstat| kstat.ks_module.starts_with("cpu_")));
+        assert!(matches!(
+            ctl.filter_glob(None, Some("zero"), None),
+            Err(Error::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn named_data_into_owned_string() {
+        let mut buf = b"AuthenticAMD".to_vec();
+        let data = NamedData::String(std::str::from_utf8(&buf).unwrap());
+        let owned = data.into_owned_string().expect("Expected a string value");
+
+        // Overwrite the buffer the value was borrowed from, as a subsequent read would.
+        buf.fill(b'x');
+        assert_eq!(owned, "AuthenticAMD");
+        assert_eq!(buf, b"xxxxxxxxxxxx");
+        assert!(NamedData::UInt64(1).into_owned_string().is_none());
+        assert!(NamedData::Char(b"str").into_owned_string().is_none());
+    }
+
+    #[test]
+    fn raw_bytes_is_contiguous() {
+        let mut fake = FakeKstat::new("unix", 0, "raw", Type::Raw)
+            .raw(&[1, 2])
+            .raw(&[3, 4])
+            .raw(&[5, 6]);
+        let kstat = fake.kstat();
+        assert_eq!(kstat.raw_bytes().unwrap(), &[1, 2, 3, 4, 5, 6]);
+        let Ok(Data::Raw(items)) = kstat.data() else {
+            panic!("Expected raw data");
+        };
+        assert_eq!(items, vec![&[1, 2][..], &[3, 4][..], &[5, 6][..]]);
+
+        fake.ks.ks_data = std::ptr::null_mut();
+        let kstat = Kstat::try_from(&fake.ks).expect("Failed to build kstat");
+        assert!(matches!(kstat.raw_bytes(), Err(Error::NullData)));
+    }
+
+    #[test]
+    fn raw_as_typed_values() {
+        let fake = FakeKstat::new("unix", 0, "raw", Type::Raw)
+            .raw(&0x0102_0304_0506_0708u64.to_ne_bytes());
+        let kstat = fake.kstat();
+        assert_eq!(kstat.raw_as::<u64>().unwrap(), &0x0102_0304_0506_0708);
+        assert_eq!(kstat.raw_as_slice::<u32>().unwrap().len(), 2);
+        assert!(matches!(
+            kstat.raw_as::<u32>(),
+            Err(Error::SizeMismatch {
+                expected: 4,
+                found: 8
+            })
+        ));
+        assert!(matches!(
+            kstat.raw_as_slice::<[u8; 3]>(),
+            Err(Error::SizeMismatch { found: 8, .. })
+        ));
+
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        struct Pair {
+            lo: u32,
+            hi: u32,
+        }
+        unsafe impl Pod for Pair {}
+        let pair = kstat.raw_as::<Pair>().unwrap();
+        assert_eq!([pair.lo, pair.hi], kstat.raw_as::<[u32; 2]>().unwrap()[..]);
+    }
+
+    #[test]
+    fn raw_ctl_round_trip() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let count = ctl.iter().count();
+        let raw = ctl.into_raw();
+        assert!(!raw.is_null());
+        let ctl = unsafe { Ctl::from_raw(raw) };
+        assert_eq!(ctl.iter().count(), count);
+    }
+
+    #[test]
+    fn data_len() {
+        let io = Io {
+            nread: 0,
+            nwritten: 0,
+            reads: 0,
+            writes: 0,
+            wtime: 0,
+            wlentime: 0,
+            wlastupdate: 0,
+            rtime: 0,
+            rlentime: 0,
+            rlastupdate: 0,
+            wcnt: 0,
+            rcnt: 0,
+        };
+        let intr = Intr::default();
+        let timer = Timer {
+            name: "timer",
+            num_events: 0,
+            elapsed_time: 0,
+            min_time: 0,
+            max_time: 0,
+            start_time: 0,
+            stop_time: 0,
+        };
+        let named = Named {
+            name: "foo",
+            value: NamedData::UInt32(0),
+        };
+        assert_eq!(Data::Raw(vec![&[0u8][..], &[1u8][..]]).len(), 2);
+        assert_eq!(
+            Data::Named(vec![named.clone(), named.clone(), named]).len(),
+            3
+        );
+        assert_eq!(Data::Intr(intr).len(), 1);
+        assert_eq!(Data::Io(io).len(), 1);
+        assert_eq!(Data::Timer(vec![timer]).len(), 1);
+        assert_eq!(Data::Null.len(), 0);
+        assert!(Data::Null.is_empty());
+        assert!(Data::Named(vec![]).is_empty());
+        assert!(!Data::Io(io).is_empty());
+    }
+
+    #[test]
+    fn data_named_iter() {
+        let io = Data::Io(Io {
+            nread: 0,
+            nwritten: 0,
+            reads: 0,
+            writes: 0,
+            wtime: 0,
+            wlentime: 0,
+            wlastupdate: 0,
+            rtime: 0,
+            rlentime: 0,
+            rlastupdate: 0,
+            wcnt: 0,
+            rcnt: 0,
+        });
+        assert!(io.named().is_none());
+        assert_eq!(io.as_named_iter().count(), 0);
+
+        let named = Data::Named(vec![
+            Named {
+                name: "foo",
+                value: NamedData::UInt32(1),
+            },
+            Named {
+                name: "bar",
+                value: NamedData::Int64(-1),
+            },
+        ]);
+        assert_eq!(named.named().map(|n| n.len()), Some(2));
+        let names = named.as_named_iter().map(|n| n.name).collect::<Vec<_>>();
+        assert_eq!(names, ["foo", "bar"]);
+    }
+
+    // A `Ctl` over a chain of fake kstats owned by a test.
+    //
+    // The handle was not allocated by `libkstat`, so it is never closed, even if the test
+    // panics.
+    struct FakeCtl<'a> {
+        ctl: std::mem::ManuallyDrop<Ctl>,
+        _raw: Box<sys::kstat_ctl_t>,
+        _chain: PhantomData<&'a mut [FakeKstat]>,
+    }
+
+    impl<'a> std::ops::Deref for FakeCtl<'a> {
+        type Target = Ctl;
+        fn deref(&self) -> &Ctl {
+            &self.ctl
+        }
+    }
+
+    impl<'a> std::ops::DerefMut for FakeCtl<'a> {
+        fn deref_mut(&mut self) -> &mut Ctl {
+            &mut self.ctl
+        }
+    }
+
+    // Link `chain` in order, and return a `Ctl` walking it.
+    fn fake_ctl(chain: &mut [FakeKstat]) -> FakeCtl<'_> {
+        for i in 1..chain.len() {
+            let next = &mut chain[i].ks as *mut _;
+            chain[i - 1].ks.ks_next = next;
+        }
+        let mut raw = Box::new(sys::kstat_ctl_t {
+            kc_chain_id: 1,
+            kc_chain: chain
+                .first_mut()
+                .map_or(std::ptr::null_mut(), |fake| &mut fake.ks as *mut _),
+            kc_kd: -1,
+        });
+        let ctl = unsafe { Ctl::from_raw(&mut *raw) };
+        FakeCtl {
+            ctl: std::mem::ManuallyDrop::new(ctl),
+            _raw: raw,
+            _chain: PhantomData,
+        }
+    }
+
+    fn fake_named(names: &[&str]) -> FakeKstat {
+        names.iter().zip(1..).fold(
+            FakeKstat::new("unix", 0, "named", Type::Named),
+            |fake, (name, value)| fake.named(name, NamedData::UInt64(value)),
+        )
+    }
+
+    #[test]
+    fn data_into_reuses_buffer() {
+        let fake = fake_named(&["foo", "bar"]);
+        let kstat = fake.kstat();
+
+        let mut buf = DataBuf::new();
+        let DataRef::Named(named) = kstat.data_into(&mut buf).unwrap() else {
+            panic!("Expected named data");
+        };
+        assert_eq!(named.len(), 2);
+        assert_eq!(named[1].name, "bar");
+        let (ptr, capacity) = (buf.named.as_ptr(), buf.named.capacity());
+        for _ in 0..10 {
+            let data = kstat.data_into(&mut buf).unwrap();
+            assert!(matches!(data.to_data(), Data::Named(n) if n.len() == 2));
+            assert_eq!(buf.named.as_ptr(), ptr);
+            assert_eq!(buf.named.capacity(), capacity);
+        }
+    }
+
+    #[test]
+    fn lazy_kstat_accessors() {
+        let mut fake = FakeKstat::new("sd", 3, "bad?", Type::Io);
+        fake.ks.ks_name[3] = 0xff_u8 as _;
+        let lazy = LazyKstat {
+            ctl: std::ptr::null_mut(),
+            ks: &fake.ks,
+        };
+        assert_eq!(lazy.ks_type().unwrap(), Type::Io);
+        assert_eq!(lazy.module().unwrap(), "sd");
+        assert_eq!(lazy.instance(), 3);
+        assert!(matches!(
+            lazy.name(),
+            Err(Error::InvalidString {
+                field: StringField::Name
+            })
+        ));
+        assert!(matches!(
+            lazy.to_kstat(),
+            Err(Error::InvalidString {
+                field: StringField::Name
+            })
+        ));
+    }
+
+    #[test]
+    fn iter_lazy_matches_iter() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let lazy = ctl
+            .iter_lazy()
+            .filter(|kstat| matches!(kstat.ks_type(), Ok(Type::Io)))
+            .filter_map(|kstat| kstat.to_kstat().ok())
+            .collect::<Vec<_>>();
+        assert_eq!(lazy, ctl.iter_by_type(Type::Io).collect::<Vec<_>>());
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn snapshot_is_sorted() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let snapshot = ctl.snapshot().expect("Failed to snapshot kstats");
+        assert!(!snapshot.is_empty());
+        assert!(snapshot.windows(2).all(|w| w[0].0 <= w[1].0));
+        drop(ctl);
+        assert!(snapshot
+            .iter()
+            .any(|(id, _)| id == &KstatId::new("cpu_info", 0, "cpu_info0")));
+    }
+
+    #[test]
+    fn type_names_round_trip() {
+        for ty in [Type::Raw, Type::Named, Type::Intr, Type::Io, Type::Timer] {
+            assert_eq!(ty.to_string().parse::<Type>().unwrap(), ty);
+            assert_eq!(Type::try_from(ty.as_str()).unwrap(), ty);
+        }
+        assert_eq!(Type::Io.to_string(), "io");
+        assert!(matches!(
+            "bogus".parse::<Type>(),
+            Err(Error::InvalidTypeName(s)) if s == "bogus"
+        ));
+        assert_eq!("unknown(99)".parse::<Type>().unwrap(), Type::Unknown(99));
+        assert_eq!("unknown(1)".parse::<Type>().unwrap(), Type::Named);
+        for bad in ["unknown(256)", "unknown()", "unknown(1"] {
+            assert!(matches!(
+                bad.parse::<Type>(),
+                Err(Error::InvalidTypeName(_))
+            ));
+        }
+
+        for ty in [
+            NamedType::Char,
+            NamedType::Int32,
+            NamedType::UInt32,
+            NamedType::Int64,
+            NamedType::UInt64,
+            NamedType::Float,
+            NamedType::Double,
+            NamedType::Long,
+            NamedType::ULong,
+            NamedType::String,
+        ] {
+            assert_eq!(ty.to_string().parse::<NamedType>().unwrap(), ty);
+            assert_eq!(NamedType::try_from(ty.as_str()).unwrap(), ty);
+        }
+        assert_eq!(NamedType::UInt64.to_string(), "uint64");
+        assert!(matches!(
+            "Named".parse::<NamedType>(),
+            Err(Error::InvalidNamedTypeName(_))
+        ));
+    }
+
+    #[test]
+    fn type_codes_round_trip() {
+        for ty in [Type::Raw, Type::Named, Type::Intr, Type::Io, Type::Timer] {
+            assert_eq!(Type::try_from(u8::from(ty)).unwrap(), ty);
+        }
+        assert_eq!(u8::from(Type::Io), sys::KSTAT_TYPE_IO);
+        for ty in [
+            NamedType::Char,
+            NamedType::Int32,
+            NamedType::UInt32,
+            NamedType::Int64,
+            NamedType::UInt64,
+            NamedType::Float,
+            NamedType::Double,
+            NamedType::Long,
+            NamedType::ULong,
+            NamedType::String,
+        ] {
+            assert_eq!(NamedType::try_from(u8::from(ty)).unwrap(), ty);
+        }
+        assert_eq!(u8::from(NamedType::String), sys::KSTAT_DATA_STRING);
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn count_kstats() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let count = ctl.count_kstats();
+        assert!(count > 0);
+        assert!(count >= ctl.iter().count());
+        assert_eq!(count, ctl.iter_raw().count());
+    }
+
+    #[test]
+    fn zero_ndata_io_is_an_error() {
+        let mut fake = FakeKstat::new("sd", 0, "sd0", Type::Io).io(Io::default());
+        fake.ks.ks_ndata = 0;
+        let kstat = fake.kstat();
+        assert!(matches!(
+            kstat.data(),
+            Err(Error::UnexpectedDataShape {
+                expected: 1,
+                found: 0
+            })
+        ));
+        let mut buf = DataBuf::new();
+        assert!(matches!(
+            kstat.data_into(&mut buf),
+            Err(Error::UnexpectedDataShape {
+                expected: 1,
+                found: 0
+            })
+        ));
+
+        // A populated kstat with the wrong size is also rejected.
+        fake.ks.ks_ndata = 1;
+        fake.ks.ks_data_size = 1;
+        assert!(matches!(
+            fake.kstat().data(),
+            Err(Error::SizeMismatch { found: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn null_data_pointer_is_an_error() {
+        for mut fake in [
+            FakeKstat::new("sd", 0, "sd0", Type::Io).io(Io::default()),
+            FakeKstat::new("pci", 0, "pci0", Type::Intr).intr(Intr {
+                hard: 0,
+                soft: 0,
+                watchdog: 0,
+                spurious: 0,
+                multisvc: 0,
+            }),
+        ] {
+            fake.ks.ks_data = std::ptr::null_mut();
+            let kstat = fake.kstat();
+            assert!(matches!(kstat.data(), Err(Error::NullData)));
+            let mut buf = DataBuf::new();
+            assert!(matches!(kstat.data_into(&mut buf), Err(Error::NullData)));
+        }
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn read_all() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let mut successes = 0;
+        let mut total = 0;
+        for (kstat, data) in ctl.read_all() {
+            total += 1;
+            if data.is_ok() {
+                assert!(kstat.is_read());
+                successes += 1;
+            }
+        }
+        assert!(successes > 0);
+        assert_eq!(total, ctl.iter().count());
+    }
+
+    #[test]
+    fn kstat_getters() {
+        let kstat = fake_kstat("misc", "cpu_info", 3, "cpu_info3", 10);
+        assert_eq!(kstat.module(), "cpu_info");
+        assert_eq!(kstat.instance(), 3);
+        assert_eq!(kstat.name(), "cpu_info3");
+        assert_eq!(kstat.class(), "misc");
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn grouped_cpu_info() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let groups = ctl.grouped().expect("Failed to group kstats");
+        let cpu_info = groups.get("cpu_info").expect("Missing cpu_info module");
+        assert!(!cpu_info.is_empty());
+        for (instance, names) in cpu_info.iter() {
+            let name = format!("cpu_info{}", instance);
+            assert!(matches!(names.get(&name), Some(OwnedData::Named(_))));
+        }
+    }
+
+    #[test]
+    fn named_data_counter_delta() {
+        assert_eq!(
+            NamedData::UInt64(15).counter_delta(&NamedData::UInt64(10)),
+            Some(5)
+        );
+        assert_eq!(
+            NamedData::UInt64(4).counter_delta(&NamedData::UInt64(u64::MAX - 5)),
+            Some(10)
+        );
+        assert_eq!(
+            NamedData::UInt32(4).counter_delta(&NamedData::UInt32(u32::MAX - 5)),
+            Some(10)
+        );
+        assert_eq!(
+            NamedData::Int32(i32::MIN).counter_delta(&NamedData::Int32(i32::MAX)),
+            Some(1)
+        );
+        assert_eq!(
+            NamedData::Int64(i64::MIN + 1).counter_delta(&NamedData::Int64(i64::MAX)),
+            Some(2)
+        );
+        assert_eq!(
+            NamedData::UInt64(1).counter_delta(&NamedData::UInt32(0)),
+            None
+        );
+        assert_eq!(
+            NamedData::String("a").counter_delta(&NamedData::String("a")),
+            None
+        );
+    }
+
+    #[test]
+    fn unknown_type_is_enumerated() {
+        let mut chain = [FakeKstat::new("future", 0, "", Type::Unknown(99))];
+        let ctl = fake_ctl(&mut chain);
+        let kstats = ctl.iter().collect::<Vec<_>>();
+        assert_eq!(kstats.len(), 1);
+        assert_eq!(kstats[0].ks_module, "future");
+        assert_eq!(kstats[0].ks_type, Type::Unknown(99));
+        assert_eq!(kstats[0].ks_type.to_string(), "unknown(99)");
+        assert_eq!(
+            kstats[0].ks_type.to_string().parse::<Type>().unwrap(),
+            Type::Unknown(99)
+        );
+        assert_eq!(u8::from(kstats[0].ks_type), 99);
+
+        let mut kstat = kstats[0];
+        kstat.read = true;
+        assert!(matches!(kstat.data(), Err(Error::InvalidType(99))));
+    }
+
+    #[test]
+    fn data_content_hash() {
+        let named = |value| {
+            Data::Named(vec![
+                Named {
+                    name: "a",
+                    value: NamedData::UInt64(1),
+                },
+                Named {
+                    name: "b",
+                    value: NamedData::UInt64(value),
+                },
+            ])
+        };
+        assert_eq!(named(2).content_hash(), named(2).content_hash());
+        assert_ne!(named(2).content_hash(), named(3).content_hash());
+        assert_ne!(named(2).content_hash(), Data::Null.content_hash());
+    }
+
+    #[test]
+    fn named_subset_preserves_order() {
+        let fake = fake_named(&["foo", "bar", "baz", "quux"]);
+        let kstat = fake.kstat();
+
+        let subset = kstat.named_subset(&["quux", "bar", "missing"]).unwrap();
+        assert_eq!(subset.len(), 2);
+        assert_eq!(subset[0].name, "bar");
+        assert!(matches!(subset[0].value, NamedData::UInt64(2)));
+        assert_eq!(subset[1].name, "quux");
+        assert!(matches!(subset[1].value, NamedData::UInt64(4)));
+    }
+
+    #[test]
+    fn named_subset_ignores_unselected_invalid_names() {
+        let fake = fake_named(&["foo", "bar"]);
+        // Make the first name invalid UTF-8.
+        unsafe { (*(fake.ks.ks_data as *mut sys::kstat_named_t)).name[0] = 0xff_u8 as _ };
+        let kstat = fake.kstat();
+        let subset = kstat.named_subset(&["bar"]).unwrap();
+        assert_eq!(subset.len(), 1);
+        assert_eq!(subset[0].name, "bar");
+        assert!(matches!(
+            kstat.named_subset(&["\u{fffd}oo", "bar"]),
+            Ok(subset) if subset.len() == 1
+        ));
+
+        let io = FakeKstat::new("sd", 0, "sd0", Type::Io).io(Io::default());
+        assert!(matches!(
+            io.kstat().named_subset(&["bar"]),
+            Err(Error::WrongType {
+                expected: Type::Named,
+                found: Type::Io
+            })
+        ));
+    }
+
+    #[test]
+    fn data_equality() {
+        let named = |value| {
+            Data::Named(vec![Named {
+                name: "foo",
+                value: NamedData::UInt64(value),
+            }])
+        };
+        assert_eq!(named(1), named(1));
+        assert_ne!(named(1), named(2));
+        assert_ne!(named(1), Data::Null);
+        assert_eq!(named(1).to_owned(), named(1).to_owned());
+    }
+
+    #[test]
+    fn ndata_matches_named_data() {
+        let fake = fake_named(&["foo", "bar"]);
+        let kstat = fake.kstat();
+        let data = kstat.data().unwrap();
+        assert_eq!(kstat.ndata() as usize, data.len());
+        assert_eq!(
+            kstat.data_size(),
+            2 * std::mem::size_of::<sys::kstat_named_t>()
+        );
+        assert_eq!(fake_kstat("misc", "foo", 0, "bar", 0).ndata(), 0);
+    }
+
+    #[test]
+    fn removed_kstat_errors_are_gone() {
+        for errno in [libc::ENXIO, libc::ENOENT] {
+            let err = std::io::Error::from_raw_os_error(errno);
+            assert!(matches!(kstat_error(err), Error::Gone));
+        }
+        let err = std::io::Error::from_raw_os_error(libc::EIO);
+        assert!(matches!(kstat_error(err), Error::Io(e) if e.raw_os_error() == Some(libc::EIO)));
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn try_clone_is_independent() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let clone = ctl.try_clone().expect("Failed to clone kstat control");
+        assert_ne!(ctl.ctl, clone.ctl);
+        assert!(ctl.iter().count() > 0);
+        assert!(clone.iter().count() > 0);
+        drop(ctl);
+        assert!(clone.iter().count() > 0);
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn kstat_time_durations() {
+        let mut kstat = fake_kstat("misc", "foo", 0, "bar", -10);
+        kstat.ks_snaptime = 1_500;
+        assert_eq!(kstat.crtime_duration(), Duration::ZERO);
+        assert_eq!(kstat.snaptime_duration(), Duration::from_nanos(1_500));
+        assert_eq!(kstat.uptime_at_snapshot(), kstat.snaptime_duration());
+
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let mut kstat = ctl
+            .filter(Some("cpu_info"), Instance::Num(0), None)
+            .next()
+            .expect("Failed to find cpu_info kstat");
+        ctl.read(&mut kstat).expect("Failed to read kstat");
+        assert!(kstat.snaptime_duration() >= kstat.crtime_duration());
+    }
+
+    #[test]
+    fn hrtime_clock_is_near_now() {
+        let clock = HrtimeClock::new();
+        let now = SystemTime::now();
+        let converted = clock.to_system_time(gethrtime()).unwrap();
+        let diff = converted
+            .duration_since(now)
+            .unwrap_or_else(|e| e.duration());
+        assert!(diff < Duration::from_secs(5));
+
+        let earlier = clock.to_system_time(gethrtime() - 60_000_000_000);
+        assert!(earlier.unwrap() < now);
+        assert!(clock.to_system_time(i64::MIN).is_none());
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn iter_modules_groups_cpu_info() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let modules = ctl.iter_modules().collect::<Vec<_>>();
+        assert!(modules.windows(2).all(|w| w[0].0 < w[1].0));
+        let (_, cpu_info) = modules
+            .iter()
+            .find(|(module, _)| *module == "cpu_info")
+            .expect("Missing cpu_info module");
+        assert!(cpu_info.iter().all(|kstat| kstat.ks_module == "cpu_info"));
+        assert_eq!(
+            cpu_info.len(),
+            ctl.filter(Some("cpu_info"), Instance::Any, None).count()
+        );
+    }
+
+    #[test]
+    fn kstat_is_counter_class() {
+        assert!(fake_kstat("disk", "sd", 0, "sd0", 0).is_counter_class());
+        assert!(!fake_kstat("misc", "cpu_info", 0, "cpu_info0", 0).is_counter_class());
+
+        // A named kstat which has been read must also have a statistic with a counter suffix.
+        let link = |names: &[&str]| {
+            names.iter().fold(
+                FakeKstat::new("link", 0, "net0", Type::Named).class("net"),
+                |fake, name| fake.named(name, NamedData::UInt64(0)),
+            )
+        };
+        assert!(link(&["link_state", "rbytes64"]).kstat().is_counter_class());
+        assert!(!link(&["link_state"]).kstat().is_counter_class());
+        assert!(!FakeKstat::new("unix", 0, "system_misc", Type::Named)
+            .class("misc")
+            .named("rbytes64", NamedData::UInt64(0))
+            .kstat()
+            .is_counter_class());
+    }
+
+    #[test]
+    fn close() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        assert!(ctl.close().is_ok());
+    }
+
+    #[test]
+    fn named_double() {
+        let mut named: sys::kstat_named_t = unsafe { std::mem::zeroed() };
+        for (dst, src) in named.name.iter_mut().zip(b"load") {
+            *dst = *src as _;
+        }
+        named.data_type = sys::KSTAT_DATA_DOUBLE;
+        named.value.d = 1.5;
+        let named = Named::try_from(&named).expect("Failed to decode named data");
+        assert_eq!(named.name, "load");
+        assert_eq!(named.value, NamedData::Double(1.5));
+        assert_eq!(named.data_type(), NamedType::Double);
+        assert_eq!(named.value.as_f64(), Some(1.5));
+        assert_eq!(named.value.as_u64(), None);
+        assert_eq!(named.value.to_string(), "1.5");
+        assert_eq!(
+            OwnedNamedData::from(&named.value),
+            OwnedNamedData::Double(1.5)
+        );
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn named_long_widens() {
+        let mut named: sys::kstat_named_t = unsafe { std::mem::zeroed() };
+        named.data_type = sys::KSTAT_DATA_LONG;
+        named.value.l = i64::MIN as _;
+        let decoded = Named::try_from(&named).expect("Failed to decode named data");
+        assert_eq!(decoded.value, NamedData::Int64(i64::MIN));
+
+        named.data_type = sys::KSTAT_DATA_ULONG;
+        named.value.ul = u64::MAX as _;
+        let decoded = Named::try_from(&named).expect("Failed to decode named data");
+        assert_eq!(decoded.value, NamedData::UInt64(u64::MAX));
+    }
+
+    #[test]
+    fn class_is_decoded_from_class_field() {
+        let fake = FakeKstat::new("sd", 0, "sd0", Type::Io).class("disk");
+        let kstat = fake.kstat();
+        assert_eq!(kstat.ks_name, "sd0");
+        assert_eq!(kstat.ks_class, "disk");
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn filter_class() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let misc = ctl.filter_class("misc").collect::<Vec<_>>();
+        assert!(!misc.is_empty());
+        assert!(misc.iter().all(|kstat| kstat.ks_class == "misc"));
+        assert_eq!(
+            misc.len(),
+            ctl.iter().filter(|kstat| kstat.ks_class == "misc").count()
+        );
+        assert!(misc.iter().any(|kstat| kstat.ks_module == "cpu_info"));
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn read_many_preserves_order() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let ids = [
+            KstatId::new("unix", 0, "system_misc"),
+            KstatId::new("no-such-module", 0, "missing"),
+            KstatId::new("cpu_info", 0, "cpu_info0"),
+        ];
+        let results = ctl.read_many(&ids);
+        assert_eq!(results.len(), ids.len());
+        for ((id, _), expected) in results.iter().zip(ids.iter()) {
+            assert_eq!(id, expected);
+        }
+        assert!(matches!(results[0].1, Ok(OwnedData::Named(_))));
+        assert!(matches!(results[1].1, Err(Error::NotFound)));
+        assert!(matches!(results[2].1, Ok(OwnedData::Named(_))));
+    }
+
+    #[test]
+    fn instance_as_option() {
+        assert_eq!(Instance::Num(3).as_option(), Some(3));
+        assert_eq!(Instance::Num(-1).as_option(), Some(-1));
+        assert_eq!(Instance::default().as_option(), None);
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn filter_and_lookup_by_instance() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let any = ctl
+            .filter(Some("cpu_info"), Instance::Any, None)
+            .collect::<Vec<_>>();
+        assert!(!any.is_empty());
+        let zero = ctl
+            .filter(Some("cpu_info"), Instance::Num(0), None)
+            .collect::<Vec<_>>();
+        assert_eq!(zero.len(), 1);
+        assert_eq!(zero[0].ks_instance, 0);
+
+        let found = ctl
+            .lookup(Some("cpu_info"), Instance::Num(0), None)
+            .expect("Failed to look up kstat")
+            .expect("Expected a cpu_info kstat");
+        assert_eq!(found.ks_instance, 0);
+        assert!(ctl
+            .lookup(Some("cpu_info"), Instance::Any, Some("cpu_info0"))
+            .expect("Failed to look up kstat")
+            .is_some());
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn refresh_snaptime_is_monotonic() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let mut kstat = ctl
+            .filter(Some("cpu_info"), Instance::Num(0), None)
+            .next()
+            .expect("Failed to find cpu_info kstat");
+        let first = kstat.refresh(&ctl).expect("Failed to refresh kstat");
+        let second = kstat.refresh(&ctl).expect("Failed to refresh kstat");
+        assert!(kstat.is_read());
+        assert!(second >= first);
+        assert_eq!(second, kstat.ks_snaptime);
+    }
+
+    #[test]
+    fn kstat_from_other_ctl_is_rejected() {
+        let mut first = [FakeKstat::new("unix", 0, "", Type::Named)];
+        let first = fake_ctl(&mut first);
+        let second = fake_ctl(&mut []);
+        let mut kstat = first.iter().next().expect("Expected a kstat");
+        assert!(matches!(second.read(&mut kstat), Err(Error::WrongCtl)));
+        assert!(matches!(kstat.refresh(&second), Err(Error::WrongCtl)));
+    }
+
+    #[test]
+    fn chain_id_is_stable_without_update() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let id = ctl.chain_id();
+        for _ in 0..3 {
+            for mut kstat in ctl.iter() {
+                let _ = ctl.read(&mut kstat);
+            }
+            assert_eq!(ctl.chain_id(), id);
+        }
+    }
+
+    #[test]
+    fn raw_elements_as_u32() {
+        let values = [7u32, 11];
+        let bytes = unsafe {
+            std::slice::from_raw_parts(values.as_ptr().cast::<u8>(), std::mem::size_of_val(&values))
+        };
+        let data = Data::Raw(bytes.chunks(4).collect());
+        let elements = data.raw_elements::<u32>().unwrap();
+        assert_eq!(elements, [&7, &11]);
+        assert!(matches!(
+            data.raw_elements::<u64>(),
+            Err(Error::SizeMismatch {
+                expected: 8,
+                found: 4
+            })
+        ));
+        assert!(Data::Null.raw_elements::<u32>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn skip_handler_sees_malformed_kstat() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mut fake = FakeKstat::new("bad?mod", 3, "", Type::Raw);
+        fake.ks.ks_module[3] = 0xff_u8 as _;
+        let mut chain = [fake];
+        let mut ctl = fake_ctl(&mut chain);
+        let count = Arc::new(AtomicUsize::new(0));
+        let seen = Arc::clone(&count);
+        ctl.set_skip_handler(move |skipped| {
+            assert_eq!(skipped.module, b"bad\xffmod");
+            assert_eq!(skipped.instance, 3);
+            assert!(matches!(
+                skipped.reason,
+                Error::InvalidString {
+                    field: StringField::Module
+                }
+            ));
+            seen.fetch_add(1, Ordering::Relaxed);
+        });
+        assert_eq!(ctl.iter().count(), 0);
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn for_loop_over_ctl() {
+        let mut chain = ["first", "second"].map(|module| FakeKstat::new(module, 0, "", Type::Raw));
+        let ctl = fake_ctl(&mut chain);
+        let mut modules = Vec::new();
+        for kstat in &*ctl {
+            modules.push(kstat.ks_module);
+        }
+        assert_eq!(modules, ["first", "second"]);
+    }
+
+    #[test]
+    fn find_first_match_or_not_found() {
+        let mut chain =
+            [0, 1].map(|instance| FakeKstat::new("cpu_info", instance, "", Type::Named));
+        let ctl = fake_ctl(&mut chain);
+        let kstat = ctl
+            .find(Some("cpu_info"), Instance::Any, None)
+            .expect("Expected a cpu_info kstat");
+        assert_eq!(kstat.ks_instance, 0);
+        let kstat = ctl
+            .find(Some("cpu_info"), Instance::Num(1), None)
+            .expect("Expected a cpu_info kstat");
+        assert_eq!(kstat.ks_instance, 1);
+        assert!(matches!(
+            ctl.find(Some("cpu_info"), Instance::Num(2), None),
+            Err(Error::NotFound)
+        ));
+        assert!(matches!(
+            ctl.find(Some("zfs"), Instance::Any, None),
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn read_with_time_returns_snaptime() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let mut kstat = ctl
+            .find(Some("cpu_info"), Instance::Num(0), None)
+            .expect("Failed to find cpu_info kstat");
+        let (snaptime, data) = ctl
+            .read_with_time(&mut kstat)
+            .expect("Failed to read kstat");
+        assert_eq!(snaptime, kstat.ks_snaptime);
+        assert!(matches!(data, Data::Named(_)));
+    }
+
+    #[test]
+    fn named_char_is_at_most_named_char_len() {
+        let mut named: sys::kstat_named_t = unsafe { std::mem::zeroed() };
+        named.name[0] = b'c' as _;
+        named.data_type = sys::KSTAT_DATA_CHAR;
+        named.value.charc = *b"0123456789abcdef";
+        let named = Named::try_from(&named).unwrap();
+        let NamedData::Char(chars) = named.value else {
+            panic!("expected char data");
+        };
+        assert!(chars.len() <= NAMED_CHAR_LEN);
+        assert_eq!(chars, b"0123456789abcdef");
+        assert_eq!(STRLEN, sys::KSTAT_STRLEN);
+    }
+
+    #[test]
+    fn data_kind_matches_type() {
+        let intr = Intr::default();
+        assert_eq!(Data::Raw(vec![]).kind(), Some(Type::Raw));
+        assert_eq!(Data::Named(vec![]).kind(), Some(Type::Named));
+        assert_eq!(Data::Intr(intr).kind(), Some(Type::Intr));
+        assert_eq!(Data::Io(Io::default()).kind(), Some(Type::Io));
+        assert_eq!(Data::Timer(vec![]).kind(), Some(Type::Timer));
+        assert_eq!(Data::Null.kind(), None);
+    }
+
+    #[test]
+    fn named_infer_unit() {
+        let named = |name| Named {
+            name,
+            value: NamedData::UInt64(0),
+        };
+        assert_eq!(named("rbytes64").infer_unit(), Some(Unit::Bytes));
+        assert_eq!(named("current_clock_Hz").infer_unit(), Some(Unit::Hertz));
+        assert_eq!(named("crtime_nsec").infer_unit(), Some(Unit::Nanoseconds));
+        assert_eq!(named("opackets").infer_unit(), Some(Unit::Count));
+        assert_eq!(named("chip_id").infer_unit(), None);
+        let rules = UnitRules {
+            suffixes: vec![(String::from("_id"), Unit::Count)],
+        };
+        assert_eq!(named("chip_id").infer_unit_with(&rules), Some(Unit::Count));
+    }
+
+    #[test]
+    fn decode_raw_named_buffer() {
+        // The value of the string is a pointer, which can't in general be followed in a captured
+        // buffer.
+        let fake = FakeKstat::new("unix", 0, "captured", Type::Named)
+            .named("count", NamedData::UInt64(42))
+            .named("label", NamedData::String("captured"));
+        let bytes = fake.kstat().raw_bytes().unwrap().to_vec();
+        let data = Data::decode_raw(Type::Named, 2, &bytes).unwrap();
+        assert_eq!(
+            data,
+            OwnedData::Named(vec![
+                OwnedNamed {
+                    name: String::from("count"),
+                    value: OwnedNamedData::UInt64(42),
+                },
+                OwnedNamed {
+                    name: String::from("label"),
+                    value: OwnedNamedData::String(String::new()),
+                },
+            ])
+        );
+        assert!(matches!(
+            Data::decode_raw(Type::Unknown(99), 0, &[]),
+            Err(Error::InvalidType(99))
+        ));
+
+        // A name which fills its array without a NUL is rejected, rather than read past.
+        let mut bytes = bytes;
+        bytes[..STRLEN].fill(b'x');
+        assert!(matches!(
+            Data::decode_raw(Type::Named, 2, &bytes),
+            Err(Error::InvalidString {
+                field: StringField::StatName
+            })
+        ));
+    }
+
+    #[test]
+    fn partial_trailing_element_is_rejected() {
+        let timer_size = std::mem::size_of::<sys::kstat_timer_t>();
+        let bytes = vec![0u8; timer_size * 2];
+        assert!(matches!(
+            Data::decode_raw(Type::Timer, 1, &bytes[..timer_size + 1]),
+            Err(Error::MisalignedData { size, element_size })
+                if size == timer_size + 1 && element_size == timer_size
+        ));
+
+        // The partial element is reported even when `ks_ndata` covers only the whole ones.
+        let timer = Timer {
+            name: "t",
+            num_events: 1,
+            elapsed_time: 2,
+            min_time: 3,
+            max_time: 4,
+            start_time: 5,
+            stop_time: 6,
+        };
+        let mut fake = FakeKstat::new("unix", 0, "timers", Type::Timer)
+            .timer(timer)
+            .timer(timer);
+        fake.ks.ks_ndata = 1;
+        fake.ks.ks_data_size -= 4;
+        assert!(matches!(fake.data(), Err(Error::MisalignedData { .. })));
+
+        // Raw data is split into `ks_ndata` equal items, which must cover the whole buffer.
+        assert!(matches!(
+            Data::decode_raw(Type::Raw, 2, &bytes[..9]),
+            Err(Error::MisalignedData {
+                size: 9,
+                element_size: 4
+            })
+        ));
+        let mut fake = FakeKstat::new("unix", 0, "raw", Type::Raw).raw(&[0; 9]);
+        fake.ks.ks_ndata = 2;
+        assert!(matches!(fake.data(), Err(Error::MisalignedData { .. })));
+        let mut buf = DataBuf::default();
+        assert!(matches!(
+            fake.kstat().data_into(&mut buf),
+            Err(Error::MisalignedData { .. })
+        ));
+    }
+
+    #[test]
+    fn named_strings_may_follow_the_array() {
+        let named_size = std::mem::size_of::<sys::kstat_named_t>();
+        let fake = FakeKstat::new("unix", 0, "version", Type::Named)
+            .named("release", NamedData::String("5.11"))
+            .named("count", NamedData::UInt64(3))
+            .inline_strings();
+        assert_eq!(fake.ks.ks_data_size, 2 * named_size + "5.11\0".len());
+        let Data::Named(named) = fake.data().unwrap() else {
+            panic!("Expected named data");
+        };
+        assert_eq!(named[0].value, NamedData::String("5.11"));
+        assert_eq!(named[1].value, NamedData::UInt64(3));
+
+        // Bytes after the array are not an error when decoding either.
+        let bytes = vec![0u8; named_size + 1];
+        assert!(Data::decode_raw(Type::Named, 1, &bytes).is_ok());
+
+        // But the buffer must still hold the whole array.
+        let mut fake = fake_named(&["a", "b"]);
+        fake.ks.ks_ndata = 3;
+        assert!(matches!(
+            fake.data(),
+            Err(Error::SizeMismatch { expected, found })
+                if expected == 3 * named_size && found == 2 * named_size
+        ));
+    }
+
+    #[test]
+    fn iter_sorted_is_deterministic() {
+        let mut chain =
+            ["zfs", "cpu_info", "link"].map(|module| FakeKstat::new(module, 0, "", Type::Raw));
+        let ctl = fake_ctl(&mut chain);
+        let first = ctl.iter_sorted().map(|k| k.id()).collect::<Vec<_>>();
+        let second = ctl.iter_sorted().map(|k| k.id()).collect::<Vec<_>>();
+        assert_eq!(first, second);
+        let modules = first
+            .iter()
+            .map(|id| id.module.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(modules, ["cpu_info", "link", "zfs"]);
+    }
+
+    #[test]
+    fn distinct_kstats_have_distinct_kids() {
+        let mut chain = [1, 2].map(|kid| FakeKstat::new("unix", 0, "", Type::Raw).kid(kid));
+        let ctl = fake_ctl(&mut chain);
+        let kids = ctl.iter().map(|k| k.kid()).collect::<Vec<_>>();
+        assert_eq!(kids, [1, 2]);
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn debug_with_data_shows_stats() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let kstat = ctl
+            .find(Some("cpu_info"), Instance::Num(0), None)
+            .expect("Failed to find cpu_info kstat");
+        let out = kstat.debug_with_data(&ctl);
+        assert!(out.contains("cpu_info0"));
+        assert!(out.contains("clock_MHz"));
+        assert!(!kstat.is_read());
+    }
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn read_iter_reads_kstats() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let ok = ctl.read_iter().filter(Result::is_ok).count();
+        assert!(ok > 0);
+        assert!(ok <= ctl.iter().count());
+    }
+
+    #[test]
+    fn timer_durations() {
+        let timer = Timer {
+            name: "t",
+            num_events: 4,
+            elapsed_time: 1_000,
+            min_time: 100,
+            max_time: 400,
+            start_time: 0,
+            stop_time: 0,
+        };
+        assert_eq!(timer.elapsed(), Duration::from_nanos(1_000));
+        assert_eq!(timer.min(), Duration::from_nanos(100));
+        assert_eq!(timer.max(), Duration::from_nanos(400));
+        assert_eq!(timer.average(), Duration::from_nanos(250));
+        let idle = Timer {
+            num_events: 0,
+            elapsed_time: 0,
+            ..timer
+        };
+        assert_eq!(idle.average(), Duration::ZERO);
+        let negative = Timer {
+            min_time: -1,
+            ..timer
+        };
+        assert_eq!(negative.min(), Duration::ZERO);
+    }
+
+    #[test]
+    fn invalid_string_identifies_field() {
+        let mut fake = FakeKstat::new("unix", 0, "badclass", Type::Raw);
+        fake.ks.ks_class[0] = 0xff_u8 as _;
+        let err = Kstat::try_from(&fake.ks).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidString {
+                field: StringField::Class
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "The byte-string in the kstat class is not a valid Rust string"
+        );
+
+        let mut named: sys::kstat_named_t = unsafe { std::mem::zeroed() };
+        named.name[0] = 0xff_u8 as _;
+        assert!(matches!(
+            Named::try_from(&named),
+            Err(Error::InvalidString {
+                field: StringField::StatName
+            })
+        ));
+
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        assert!(matches!(
+            ctl.lookup(Some("nul\0module"), Instance::Any, None),
+            Err(Error::NulInArgument)
+        ));
+    }
+
+    #[test]
+    fn index_named_by_name() {
+        let stats = [
+            Named {
+                name: "clock_MHz",
+                value: NamedData::Int64(2000),
+            },
+            Named {
+                name: "state",
+                value: NamedData::String("on-line"),
+            },
+            Named {
+                name: "clock_MHz",
+                value: NamedData::Int64(3000),
+            },
+        ];
+        let index = index_by_name(&stats);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index["state"], &NamedData::String("on-line"));
+        assert_eq!(index["clock_MHz"], &NamedData::Int64(2000));
+        assert!(!index.contains_key("pg_id"));
+    }
+}
+
+// Built when the `std` feature is disabled, so that the kstat data types are checked to be usable
+// without `std`, with `cargo test --no-default-features`.
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_test {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn types_from_str_and_code() {
+        assert_eq!(Type::try_from(sys::KSTAT_TYPE_IO).unwrap(), Type::Io);
+        assert_eq!("timer".parse::<Type>().unwrap(), Type::Timer);
+        assert_eq!(u8::from(Type::Unknown(42)), 42);
+        assert_eq!(
+            NamedType::try_from(sys::KSTAT_DATA_UINT64).unwrap(),
+            NamedType::UInt64
+        );
+        assert!(matches!(
+            "bogus".parse::<NamedType>(),
+            Err(Error::InvalidNamedTypeName(_))
+        ));
+        assert_eq!(
+            Error::InvalidType(42).to_string(),
+            "Kstat type 42 is invalid"
+        );
+    }
+
+    #[test]
+    fn data_from_raw_structs() {
+        let mut io: sys::kstat_io_t = unsafe { core::mem::zeroed() };
+        io.nread = 4096;
+        io.reads = 2;
+        let io = Io::from(&io);
+        assert_eq!(io.delta(&Io::default()).nread, 4096);
+
+        let mut intr: sys::kstat_intr_t = unsafe { core::mem::zeroed() };
+        intr.intr_soft = 3;
+        assert_eq!(Intr::from(&intr).get(IntrType::Soft), 3);
+
+        let mut timer: sys::kstat_timer_t = unsafe { core::mem::zeroed() };
+        timer.name[..3].copy_from_slice(&[b'r' as _, b'u' as _, b'n' as _]);
+        timer.num_events = 2;
+        timer.elapsed_time = 10;
+        let timer = Timer::try_from(&timer).unwrap();
+        assert_eq!(timer.name, "run");
+        assert_eq!(timer.average(), Duration::from_nanos(5));
+
+        let mut named: sys::kstat_named_t = unsafe { core::mem::zeroed() };
+        named.name[..2].copy_from_slice(&[b'n' as _, b'r' as _]);
+        named.data_type = sys::KSTAT_DATA_UINT64;
+        named.value.ui64 = 17;
+        let named = Named::try_from(&named).unwrap();
+        assert_eq!(named.value, NamedData::UInt64(17));
+        assert_eq!(format!("{}", named), "nr\t17");
+        assert_eq!(OwnedNamed::from(&named).value, OwnedNamedData::UInt64(17));
+    }
+
+    #[test]
+    fn decode_raw_without_std() {
+        let mut named: sys::kstat_named_t = unsafe { core::mem::zeroed() };
+        named.name[..2].copy_from_slice(&[b'n' as _, b'r' as _]);
+        named.data_type = sys::KSTAT_DATA_UINT32;
+        named.value.ui32 = 7;
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                (&named as *const sys::kstat_named_t).cast::<u8>(),
+                core::mem::size_of::<sys::kstat_named_t>(),
+            )
+        };
+        assert_eq!(
+            Data::decode_raw(Type::Named, 1, bytes).unwrap(),
+            OwnedData::Named(alloc::vec![OwnedNamed {
+                name: String::from("nr"),
+                value: OwnedNamedData::UInt32(7),
+            }])
+        );
+        assert!(matches!(
+            Data::decode_raw(Type::Named, 2, bytes),
+            Err(Error::SizeMismatch { .. })
+        ));
+    }
 }
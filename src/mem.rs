@@ -0,0 +1,86 @@
+//! Helpers for extracting memory statistics from the `unix:0:system_pages` kstat.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Error;
+
+/// The page counts of system memory, from the `unix:0:system_pages` kstat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SystemPages {
+    /// The number of pages of physical memory.
+    pub physmem: u64,
+    /// The number of free pages.
+    pub freemem: u64,
+    /// The number of free pages, as counted by the page allocator.
+    pub pagesfree: u64,
+    /// The number of locked pages.
+    pub pageslocked: u64,
+    /// The size of a page, in bytes, from `sysconf(3C)`.
+    pub page_size: u64,
+}
+
+impl SystemPages {
+    /// Convert a count of `pages`, such as [`SystemPages::freemem`], into bytes.
+    pub fn bytes(&self, pages: u64) -> u64 {
+        pages.saturating_mul(self.page_size)
+    }
+}
+
+/// Read the page counts of system memory.
+///
+/// This returns [`Error::NotFound`] if there is no `unix:0:system_pages` kstat, or it lacks one
+/// of the page counts of [`SystemPages`].
+pub fn system_pages(ctl: &Ctl) -> Result<SystemPages, Error> {
+    let data = ctl.read_by_name("unix", 0, "system_pages")?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if page_size == -1 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(SystemPages {
+        physmem: data.named_u64("physmem")?,
+        freemem: data.named_u64("freemem")?,
+        pagesfree: data.named_u64("pagesfree")?,
+        pageslocked: data.named_u64("pageslocked")?,
+        page_size: page_size as u64,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn free_memory_within_physical() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let pages = system_pages(&ctl).expect("Failed to read system pages");
+        assert!(pages.freemem <= pages.physmem);
+    }
+
+    #[test]
+    fn bytes_multiplies_by_page_size() {
+        let pages = SystemPages {
+            physmem: 0,
+            freemem: 0,
+            pagesfree: 0,
+            pageslocked: 0,
+            page_size: 4096,
+        };
+        assert_eq!(pages.bytes(3), 3 * 4096);
+        assert_eq!(pages.bytes(u64::MAX), u64::MAX);
+    }
+}
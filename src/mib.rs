@@ -0,0 +1,157 @@
+//! Helpers for extracting the TCP and IP MIB-II counters reported by `netstat -s`.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Error;
+
+/// Commonly-used TCP statistics, from the `tcp:0:tcp` kstat.
+///
+/// Statistics which are missing from the kstat are reported as zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TcpMib {
+    /// Number of active opens, from `activeOpens`.
+    pub active_opens: u64,
+    /// Number of passive opens, from `passiveOpens`.
+    pub passive_opens: u64,
+    /// Number of failed connection attempts, from `attemptFails`.
+    pub attempt_fails: u64,
+    /// Number of connections reset from an established state, from `estabResets`.
+    pub estab_resets: u64,
+    /// Number of connections currently established, from `currEstab`.
+    pub curr_estab: u64,
+    /// Number of segments received, from `inSegs`.
+    pub in_segs: u64,
+    /// Number of segments sent, from `outSegs`.
+    pub out_segs: u64,
+    /// Number of segments retransmitted, from `retransSegs`.
+    pub retrans_segs: u64,
+    /// Number of bytes retransmitted, from `retransBytes`.
+    pub retrans_bytes: u64,
+    /// Number of segments sent with the RST flag, from `outRsts`.
+    pub out_rsts: u64,
+    /// Number of segments received in error, from `inErrs`.
+    pub in_errs: u64,
+}
+
+/// Commonly-used IP statistics, from the `ip:0:ip` kstat.
+///
+/// Statistics which are missing from the kstat are reported as zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IpMib {
+    /// Number of datagrams received, from `inReceives`.
+    pub in_receives: u64,
+    /// Number of datagrams discarded for header errors, from `inHdrErrors`.
+    pub in_hdr_errors: u64,
+    /// Number of datagrams discarded for address errors, from `inAddrErrors`.
+    pub in_addr_errors: u64,
+    /// Number of datagrams forwarded, from `forwDatagrams`.
+    pub forw_datagrams: u64,
+    /// Number of datagrams discarded for an unknown protocol, from `inUnknownProtos`.
+    pub in_unknown_protos: u64,
+    /// Number of received datagrams discarded, from `inDiscards`.
+    pub in_discards: u64,
+    /// Number of datagrams delivered to upper layers, from `inDelivers`.
+    pub in_delivers: u64,
+    /// Number of datagrams sent, from `outRequests`.
+    pub out_requests: u64,
+    /// Number of outgoing datagrams discarded, from `outDiscards`.
+    pub out_discards: u64,
+    /// Number of outgoing datagrams discarded for lack of a route, from `outNoRoutes`.
+    pub out_no_routes: u64,
+    /// Number of datagrams reassembled, from `reasmOKs`.
+    pub reasm_oks: u64,
+    /// Number of failed reassemblies, from `reasmFails`.
+    pub reasm_fails: u64,
+    /// Number of datagrams fragmented, from `fragOKs`.
+    pub frag_oks: u64,
+    /// Number of datagrams which could not be fragmented, from `fragFails`.
+    pub frag_fails: u64,
+}
+
+/// Read the TCP statistics.
+///
+/// This returns [`Error::NotFound`] if there is no `tcp:0:tcp` kstat.
+pub fn tcp_mib(ctl: &Ctl) -> Result<TcpMib, Error> {
+    let data = ctl.read_by_name("tcp", 0, "tcp")?;
+    let stat = |name| data.named_u64(name).unwrap_or(0);
+    Ok(TcpMib {
+        active_opens: stat("activeOpens"),
+        passive_opens: stat("passiveOpens"),
+        attempt_fails: stat("attemptFails"),
+        estab_resets: stat("estabResets"),
+        curr_estab: stat("currEstab"),
+        in_segs: stat("inSegs"),
+        out_segs: stat("outSegs"),
+        retrans_segs: stat("retransSegs"),
+        retrans_bytes: stat("retransBytes"),
+        out_rsts: stat("outRsts"),
+        in_errs: stat("inErrs"),
+    })
+}
+
+/// Read the IP statistics.
+///
+/// This returns [`Error::NotFound`] if there is no `ip:0:ip` kstat.
+pub fn ip_mib(ctl: &Ctl) -> Result<IpMib, Error> {
+    let data = ctl.read_by_name("ip", 0, "ip")?;
+    let stat = |name| data.named_u64(name).unwrap_or(0);
+    Ok(IpMib {
+        in_receives: stat("inReceives"),
+        in_hdr_errors: stat("inHdrErrors"),
+        in_addr_errors: stat("inAddrErrors"),
+        forw_datagrams: stat("forwDatagrams"),
+        in_unknown_protos: stat("inUnknownProtos"),
+        in_discards: stat("inDiscards"),
+        in_delivers: stat("inDelivers"),
+        out_requests: stat("outRequests"),
+        out_discards: stat("outDiscards"),
+        out_no_routes: stat("outNoRoutes"),
+        reasm_oks: stat("reasmOKs"),
+        reasm_fails: stat("reasmFails"),
+        frag_oks: stat("fragOKs"),
+        frag_fails: stat("fragFails"),
+    })
+}
+
+// These read the real kstat chain, which the stub backend hides.
+#[cfg(all(test, not(feature = "stubs")))]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::net::TcpStream;
+
+    #[test]
+    fn tcp_mib_counts_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener");
+        let mut stream =
+            TcpStream::connect(listener.local_addr().unwrap()).expect("Failed to connect");
+        stream.write_all(b"hello").expect("Failed to write");
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let tcp = tcp_mib(&ctl).expect("Failed to read TCP MIB");
+        assert!(tcp.active_opens > 0);
+        assert!(tcp.out_segs > 0);
+    }
+
+    #[test]
+    fn ip_mib_counts_datagrams() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let ip = ip_mib(&ctl).expect("Failed to read IP MIB");
+        assert!(ip.in_receives > 0 || ip.out_requests > 0);
+    }
+}
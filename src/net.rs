@@ -0,0 +1,102 @@
+//! Helpers for extracting network link statistics from `link` kstats.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Error;
+
+/// Common counters of a network link, from its `link:0:<name>` kstat.
+///
+/// Counters which are missing from the kstat are reported as zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LinkStats {
+    /// Bytes received, from `rbytes64`.
+    pub rbytes: u64,
+    /// Bytes sent, from `obytes64`.
+    pub obytes: u64,
+    /// Packets received, from `ipackets64`.
+    pub ipackets: u64,
+    /// Packets sent, from `opackets64`.
+    pub opackets: u64,
+    /// Receive errors, from `ierrors`.
+    pub ierrors: u64,
+    /// Send errors, from `oerrors`.
+    pub oerrors: u64,
+    /// Received packets dropped for lack of buffers, from `norcvbuf`.
+    pub norcvbuf: u64,
+    /// Sent packets dropped for lack of buffers, from `noxmtbuf`.
+    pub noxmtbuf: u64,
+}
+
+/// Read the statistics of the network link named `link`.
+///
+/// This returns [`Error::NotFound`] if there is no kstat for the link.
+pub fn link_stats(ctl: &Ctl, link: &str) -> Result<LinkStats, Error> {
+    let data = ctl.read_by_name("link", 0, link)?;
+    Ok(LinkStats {
+        rbytes: data.named_u64("rbytes64").unwrap_or(0),
+        obytes: data.named_u64("obytes64").unwrap_or(0),
+        ipackets: data.named_u64("ipackets64").unwrap_or(0),
+        opackets: data.named_u64("opackets64").unwrap_or(0),
+        ierrors: data.named_u64("ierrors").unwrap_or(0),
+        oerrors: data.named_u64("oerrors").unwrap_or(0),
+        norcvbuf: data.named_u64("norcvbuf").unwrap_or(0),
+        noxmtbuf: data.named_u64("noxmtbuf").unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "stubs")]
+    #[test]
+    fn link_stats_for_stub_link() {
+        use crate::FakeKstat;
+        use crate::NamedData;
+        use crate::Type;
+
+        let _chain = crate::stub::set_chain(vec![FakeKstat::new("link", 0, "net0", Type::Named)
+            .class("net")
+            .named("rbytes64", NamedData::UInt64(4096))
+            .named("obytes64", NamedData::UInt64(2048))
+            .named("ipackets64", NamedData::UInt64(8))
+            .named("opackets64", NamedData::UInt64(4))
+            .named("ierrors", NamedData::UInt32(1))
+            .named("link_state", NamedData::UInt32(1))]);
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        assert_eq!(
+            link_stats(&ctl, "net0").expect("Failed to read link stats"),
+            LinkStats {
+                rbytes: 4096,
+                obytes: 2048,
+                ipackets: 8,
+                opackets: 4,
+                ierrors: 1,
+                ..LinkStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn link_stats_missing_link() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        assert!(matches!(
+            link_stats(&ctl, "no-such-link0"),
+            Err(Error::NotFound)
+        ));
+    }
+}
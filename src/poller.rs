@@ -0,0 +1,77 @@
+//! Polling kstats from an async runtime.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Error;
+use crate::KstatId;
+use crate::OwnedData;
+
+/// Periodically read kstats without blocking an async runtime.
+///
+/// The calls into `libkstat` are made on tokio's blocking thread pool, with the `Ctl` moved to
+/// the blocking task for the duration of each poll. This is sound because [`Ctl`] is `Send`.
+#[derive(Debug)]
+pub struct Poller {
+    // This is `None` only if a previous poll was cancelled or panicked.
+    ctl: Option<Ctl>,
+}
+
+impl Poller {
+    /// Create a `Poller` which reads kstats through `ctl`.
+    pub fn new(ctl: Ctl) -> Self {
+        Poller { ctl: Some(ctl) }
+    }
+
+    /// Bring the kstat chain up to date, and read a snapshot of every kstat in it.
+    ///
+    /// The snapshot is the same as that returned by [`Ctl::snapshot`]. If a previous call was
+    /// cancelled before completing, a new control handle is opened.
+    pub async fn poll(&mut self) -> Result<Vec<(KstatId, OwnedData)>, Error> {
+        let mut ctl = match self.ctl.take() {
+            Some(ctl) => ctl,
+            None => Ctl::new()?,
+        };
+        let (ctl, snapshot) = tokio::task::spawn_blocking(move || {
+            let snapshot = ctl.update_mut().and_then(|_| ctl.snapshot());
+            (ctl, snapshot)
+        })
+        .await
+        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        self.ctl = Some(ctl);
+        snapshot
+    }
+
+    /// Consume the `Poller`, returning its control handle, if it still has one.
+    pub fn into_inner(self) -> Option<Ctl> {
+        self.ctl
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn poll_twice() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let mut poller = Poller::new(ctl);
+        for _ in 0..2 {
+            let snapshot = poller.poll().await.expect("Failed to poll kstats");
+            assert!(snapshot.windows(2).all(|w| w[0].0 <= w[1].0));
+        }
+        assert!(poller.into_inner().is_some());
+    }
+}
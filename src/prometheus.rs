@@ -0,0 +1,430 @@
+//! Export of kstats in the Prometheus text exposition format.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Data;
+use crate::KstatId;
+use crate::NamedData;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Render the data of a kstat in the Prometheus text exposition format.
+///
+/// Each statistic becomes a metric named `kstat_<module>_<statistic>`, with any characters not
+/// valid in a metric name, such as `:` and `-`, replaced by `_`. The `module`, `instance`, and
+/// `name` of the kstat are attached as labels. Named statistics are exported as untyped metrics,
+/// and `Char` and `String` values are skipped. The cumulative fields of I/O, interrupt, and timer
+/// kstats are exported as counters, with a `_total` suffix. Raw kstats produce no output.
+///
+/// Kstats of the same module share metric names, so the output for several kstats must not be
+/// concatenated, as each would repeat the `# TYPE` lines. Use [`to_prometheus_all`] instead.
+pub fn to_prometheus(id: &KstatId, data: &Data<'_>) -> String {
+    to_prometheus_all([(id, data)])
+}
+
+/// Render the data of a set of kstats in the Prometheus text exposition format.
+///
+/// Metrics are named and labelled as with [`to_prometheus`]. The `# TYPE` line of each metric is
+/// emitted once, followed by the samples of every kstat for that metric, so the output is a valid
+/// scrape body.
+pub fn to_prometheus_all<'a, 'd: 'a>(
+    kstats: impl IntoIterator<Item = (&'a KstatId, &'a Data<'d>)>,
+) -> String {
+    let mut out = Metrics::default();
+    for (id, data) in kstats {
+        out.render(id, data);
+    }
+    out.finish()
+}
+
+// Accumulates the samples of each metric family, in the order the families are first seen.
+#[derive(Default)]
+struct Metrics {
+    families: Vec<Family>,
+    index: HashMap<String, usize>,
+}
+
+// The `# TYPE` of a metric, and the lines of all its samples.
+struct Family {
+    name: String,
+    kind: &'static str,
+    samples: String,
+}
+
+impl Metrics {
+    fn render(&mut self, id: &KstatId, data: &Data<'_>) {
+        match data {
+            Data::Named(named) => {
+                for n in named.iter() {
+                    let value = match n.value {
+                        NamedData::Int32(i) => i.to_string(),
+                        NamedData::UInt32(u) => u.to_string(),
+                        NamedData::Int64(i) => i.to_string(),
+                        NamedData::UInt64(u) => u.to_string(),
+                        NamedData::Float(f) => float_value(f64::from(f)),
+                        NamedData::Double(d) => float_value(d),
+                        NamedData::Char(_) | NamedData::String(_) => continue,
+                    };
+                    self.push(id, n.name, "untyped", None, &value);
+                }
+            }
+            Data::Io(io) => {
+                self.push(id, "nread_total", "counter", None, &io.nread.to_string());
+                self.push(
+                    id,
+                    "nwritten_total",
+                    "counter",
+                    None,
+                    &io.nwritten.to_string(),
+                );
+                self.push(id, "reads_total", "counter", None, &io.reads.to_string());
+                self.push(id, "writes_total", "counter", None, &io.writes.to_string());
+                self.push(id, "wtime_total", "counter", None, &io.wtime.to_string());
+                self.push(
+                    id,
+                    "wlentime_total",
+                    "counter",
+                    None,
+                    &io.wlentime.to_string(),
+                );
+                self.push(id, "rtime_total", "counter", None, &io.rtime.to_string());
+                self.push(
+                    id,
+                    "rlentime_total",
+                    "counter",
+                    None,
+                    &io.rlentime.to_string(),
+                );
+                self.push(
+                    id,
+                    "wlastupdate",
+                    "gauge",
+                    None,
+                    &io.wlastupdate.to_string(),
+                );
+                self.push(
+                    id,
+                    "rlastupdate",
+                    "gauge",
+                    None,
+                    &io.rlastupdate.to_string(),
+                );
+                self.push(id, "wcnt", "gauge", None, &io.wcnt.to_string());
+                self.push(id, "rcnt", "gauge", None, &io.rcnt.to_string());
+            }
+            Data::Intr(intr) => {
+                self.push(
+                    id,
+                    "intr_hard_total",
+                    "counter",
+                    None,
+                    &intr.hard.to_string(),
+                );
+                self.push(
+                    id,
+                    "intr_soft_total",
+                    "counter",
+                    None,
+                    &intr.soft.to_string(),
+                );
+                self.push(
+                    id,
+                    "intr_watchdog_total",
+                    "counter",
+                    None,
+                    &intr.watchdog.to_string(),
+                );
+                self.push(
+                    id,
+                    "intr_spurious_total",
+                    "counter",
+                    None,
+                    &intr.spurious.to_string(),
+                );
+                self.push(
+                    id,
+                    "intr_multisvc_total",
+                    "counter",
+                    None,
+                    &intr.multisvc.to_string(),
+                );
+            }
+            Data::Timer(timers) => {
+                for t in timers.iter() {
+                    let timer = Some(t.name);
+                    self.push(
+                        id,
+                        "timer_events_total",
+                        "counter",
+                        timer,
+                        &t.num_events.to_string(),
+                    );
+                    self.push(
+                        id,
+                        "timer_elapsed_time_total",
+                        "counter",
+                        timer,
+                        &t.elapsed_time.to_string(),
+                    );
+                    self.push(
+                        id,
+                        "timer_min_time",
+                        "gauge",
+                        timer,
+                        &t.min_time.to_string(),
+                    );
+                    self.push(
+                        id,
+                        "timer_max_time",
+                        "gauge",
+                        timer,
+                        &t.max_time.to_string(),
+                    );
+                }
+            }
+            Data::Raw(_) | Data::Null => {}
+        }
+    }
+
+    fn push(
+        &mut self,
+        id: &KstatId,
+        stat: &str,
+        kind: &'static str,
+        timer: Option<&str>,
+        value: &str,
+    ) {
+        let metric = sanitize(&format!("kstat_{}_{}", id.module, stat));
+        let i = match self.index.get(&metric) {
+            Some(&i) => i,
+            None => {
+                self.index.insert(metric.clone(), self.families.len());
+                self.families.push(Family {
+                    name: metric,
+                    kind,
+                    samples: String::new(),
+                });
+                self.families.len() - 1
+            }
+        };
+        let family = &mut self.families[i];
+        write!(
+            family.samples,
+            "{}{{module=\"{}\",instance=\"{}\",name=\"{}\"",
+            family.name,
+            escape(&id.module),
+            id.instance,
+            escape(&id.name),
+        )
+        .unwrap();
+        if let Some(timer) = timer {
+            write!(family.samples, ",timer=\"{}\"", escape(timer)).unwrap();
+        }
+        writeln!(family.samples, "}} {}", value).unwrap();
+    }
+
+    fn finish(self) -> String {
+        let mut out = String::new();
+        for family in self.families {
+            writeln!(out, "# TYPE {} {}", family.name, family.kind).unwrap();
+            out.push_str(&family.samples);
+        }
+        out
+    }
+}
+
+// Replace any characters which are not valid in a metric name with `_`.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if c.is_ascii_alphabetic() || c == '_' || (i > 0 && c.is_ascii_digit()) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Escape a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Format a floating-point sample value, which spells infinities as `+Inf` and `-Inf`.
+fn float_value(value: f64) -> String {
+    if value.is_infinite() {
+        String::from(if value > 0.0 { "+Inf" } else { "-Inf" })
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Named;
+
+    // Check that a line is a valid metric line, `name{labels} value`.
+    fn check_metric_line(line: &str) -> (&str, &str) {
+        let (name, rest) = line.split_once('{').expect("Expected labels");
+        assert!(!name.is_empty());
+        assert!(name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+        let (labels, value) = rest.rsplit_once("} ").expect("Expected a value");
+        for label in labels.split(',') {
+            let (key, value) = label.split_once('=').expect("Expected label=value");
+            assert!(key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+            assert!(value.starts_with('"') && value.ends_with('"'));
+        }
+        value.parse::<f64>().expect("Expected a numeric value");
+        (name, labels)
+    }
+
+    #[test]
+    fn named_to_prometheus() {
+        let id = KstatId::new("link", 0, "net0");
+        let data = Data::Named(vec![
+            Named {
+                name: "rbytes64",
+                value: NamedData::UInt64(1024),
+            },
+            Named {
+                name: "link-state",
+                value: NamedData::Int32(-1),
+            },
+            Named {
+                name: "ifspeed",
+                value: NamedData::String("fast"),
+            },
+            Named {
+                name: "arch",
+                value: NamedData::Char(b"i386"),
+            },
+        ]);
+        let text = to_prometheus(&id, &data);
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "# TYPE kstat_link_rbytes64 untyped",
+                "kstat_link_rbytes64{module=\"link\",instance=\"0\",name=\"net0\"} 1024",
+                "# TYPE kstat_link_link_state untyped",
+                "kstat_link_link_state{module=\"link\",instance=\"0\",name=\"net0\"} -1",
+            ]
+        );
+        for line in lines.iter().filter(|line| !line.starts_with('#')) {
+            check_metric_line(line);
+        }
+    }
+
+    #[test]
+    fn float_to_prometheus() {
+        let id = KstatId::new("cpu", 0, "load");
+        let data = Data::Named(vec![
+            Named {
+                name: "avg",
+                value: NamedData::Double(0.5),
+            },
+            Named {
+                name: "max",
+                value: NamedData::Float(f32::INFINITY),
+            },
+        ]);
+        let text = to_prometheus(&id, &data);
+        let lines = text
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "kstat_cpu_avg{module=\"cpu\",instance=\"0\",name=\"load\"} 0.5",
+                "kstat_cpu_max{module=\"cpu\",instance=\"0\",name=\"load\"} +Inf",
+            ]
+        );
+    }
+
+    #[test]
+    fn io_to_prometheus() {
+        let id = KstatId::new("sd", 0, "sd0");
+        let data = Data::Io(crate::Io {
+            nread: 1,
+            nwritten: 2,
+            reads: 3,
+            writes: 4,
+            wtime: 5,
+            wlentime: 6,
+            wlastupdate: 7,
+            rtime: 8,
+            rlentime: 9,
+            rlastupdate: 10,
+            wcnt: 11,
+            rcnt: 12,
+        });
+        let text = to_prometheus(&id, &data);
+        assert!(text.contains("# TYPE kstat_sd_nread_total counter\n"));
+        assert!(text.contains("# TYPE kstat_sd_wcnt gauge\n"));
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            check_metric_line(line);
+        }
+    }
+
+    #[test]
+    fn set_to_prometheus_types_each_metric_once() {
+        let io = |nread| {
+            Data::Io(crate::Io {
+                nread,
+                ..crate::Io::default()
+            })
+        };
+        let ids = [KstatId::new("sd", 0, "sd0"), KstatId::new("sd", 1, "sd1")];
+        let data = [io(1), io(2)];
+        let text = to_prometheus_all(ids.iter().zip(data.iter()));
+        let types = text
+            .lines()
+            .filter(|line| line.starts_with("# TYPE"))
+            .collect::<Vec<_>>();
+        let mut unique = types.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(types.len(), unique.len());
+        assert_eq!(types.len(), 12);
+
+        // The samples of each metric follow its `# TYPE` line.
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..3],
+            [
+                "# TYPE kstat_sd_nread_total counter",
+                "kstat_sd_nread_total{module=\"sd\",instance=\"0\",name=\"sd0\"} 1",
+                "kstat_sd_nread_total{module=\"sd\",instance=\"1\",name=\"sd1\"} 2",
+            ]
+        );
+        for line in lines.iter().filter(|line| !line.starts_with('#')) {
+            check_metric_line(line);
+        }
+    }
+
+    #[test]
+    fn sanitize_metric_names() {
+        assert_eq!(sanitize("kstat_cpu:0"), "kstat_cpu_0");
+        assert_eq!(sanitize("link-state"), "link_state");
+        assert_eq!(sanitize("0abc"), "_abc");
+    }
+}
@@ -0,0 +1,220 @@
+//! Parsing of kstat selectors, as used by `kstat(8)`.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Error;
+use std::str::FromStr;
+
+/// A selector for kstats, of the form `module:instance:name:statistic`.
+///
+/// Each field is optional, and an empty or missing field matches anything. For example,
+/// `cpu_info:0::` selects all kstats in module `cpu_info` with instance 0, and `::` selects
+/// everything.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Selector {
+    /// The module of the kstat.
+    pub module: Option<String>,
+    /// The instance of the kstat.
+    pub instance: Option<i32>,
+    /// The name of the kstat.
+    pub name: Option<String>,
+    /// The name of a statistic within a named kstat.
+    pub statistic: Option<String>,
+}
+
+impl FromStr for Selector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn field(part: Option<&str>) -> Option<String> {
+            part.filter(|p| !p.is_empty()).map(String::from)
+        }
+        let mut parts = s.split(':');
+        let module = field(parts.next());
+        let instance = field(parts.next())
+            .map(|i| i.parse().map_err(|_| Error::InvalidSelector(s.to_string())))
+            .transpose()?;
+        let name = field(parts.next());
+        let statistic = field(parts.next());
+        if parts.next().is_some() {
+            return Err(Error::InvalidSelector(s.to_string()));
+        }
+        Ok(Selector {
+            module,
+            instance,
+            name,
+            statistic,
+        })
+    }
+}
+
+// A single element of a compiled glob pattern.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    // Match exactly this character.
+    Char(char),
+    // `?`, matching any single character.
+    Any,
+    // `*`, matching any sequence of characters, including an empty one.
+    Star,
+}
+
+/// A compiled shell-style glob pattern, supporting the `*` and `?` wildcards.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Pattern {
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    /// Compile a pattern from a string.
+    pub(crate) fn new(pattern: &str) -> Self {
+        let tokens = pattern
+            .chars()
+            .map(|c| match c {
+                '*' => Token::Star,
+                '?' => Token::Any,
+                c => Token::Char(c),
+            })
+            .collect();
+        Self { tokens }
+    }
+
+    /// Compile a pattern matching a kstat instance number.
+    ///
+    /// Only digits, a leading `-`, and the wildcards are allowed.
+    pub(crate) fn new_instance(pattern: &str) -> Result<Self, Error> {
+        let valid = pattern
+            .chars()
+            .enumerate()
+            .all(|(i, c)| c.is_ascii_digit() || c == '*' || c == '?' || (i == 0 && c == '-'));
+        if valid {
+            Ok(Self::new(pattern))
+        } else {
+            Err(Error::InvalidPattern(pattern.to_string()))
+        }
+    }
+
+    /// Return `true` if the pattern matches the entirety of `s`.
+    pub(crate) fn matches(&self, s: &str) -> bool {
+        let chars = s.chars().collect::<Vec<_>>();
+        let (mut t, mut c) = (0, 0);
+        // The position of the last `*` seen, and the character it is currently consuming up to.
+        let mut backtrack = None;
+        while c < chars.len() {
+            match self.tokens.get(t) {
+                Some(Token::Star) => {
+                    backtrack = Some((t, c));
+                    t += 1;
+                }
+                Some(Token::Any) => {
+                    t += 1;
+                    c += 1;
+                }
+                Some(Token::Char(ch)) if *ch == chars[c] => {
+                    t += 1;
+                    c += 1;
+                }
+                _ => match backtrack {
+                    // Let the last `*` consume one more character, and try again.
+                    Some((star, consumed)) => {
+                        t = star + 1;
+                        c = consumed + 1;
+                        backtrack = Some((star, consumed + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+        self.tokens[t..].iter().all(|tok| *tok == Token::Star)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glob_stars() {
+        let pat = Pattern::new("cpu_*");
+        assert!(pat.matches("cpu_info"));
+        assert!(pat.matches("cpu_"));
+        assert!(!pat.matches("cpu"));
+        assert!(!pat.matches("xcpu_info"));
+        assert!(Pattern::new("*").matches(""));
+        assert!(Pattern::new("*info*").matches("cpu_info0"));
+        assert!(Pattern::new("a*b*c").matches("aXbYbZc"));
+        assert!(!Pattern::new("a*b*c").matches("aXbYbZ"));
+    }
+
+    #[test]
+    fn glob_single_chars() {
+        let pat = Pattern::new("sd?");
+        assert!(pat.matches("sd0"));
+        assert!(pat.matches("sd1"));
+        assert!(!pat.matches("sd"));
+        assert!(!pat.matches("sd10"));
+        assert!(Pattern::new("cpu_info?*").matches("cpu_info12"));
+        assert!(Pattern::new("exact").matches("exact"));
+        assert!(!Pattern::new("exact").matches("exactly"));
+    }
+
+    #[test]
+    fn glob_instances() {
+        assert!(Pattern::new_instance("1?").unwrap().matches("12"));
+        assert!(Pattern::new_instance("-1").unwrap().matches("-1"));
+        assert!(matches!(
+            Pattern::new_instance("a*"),
+            Err(Error::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn parse_full_selector() {
+        let sel: Selector = "cpu_info:0:cpu_info0:".parse().unwrap();
+        assert_eq!(sel.module.as_deref(), Some("cpu_info"));
+        assert_eq!(sel.instance, Some(0));
+        assert_eq!(sel.name.as_deref(), Some("cpu_info0"));
+        assert_eq!(sel.statistic, None);
+
+        let sel: Selector = "cpu_info:0:cpu_info0:clock_MHz".parse().unwrap();
+        assert_eq!(sel.statistic.as_deref(), Some("clock_MHz"));
+    }
+
+    #[test]
+    fn parse_wildcard_selectors() {
+        assert_eq!("::".parse::<Selector>().unwrap(), Selector::default());
+        assert_eq!("".parse::<Selector>().unwrap(), Selector::default());
+        let sel: Selector = "cpu::".parse().unwrap();
+        assert_eq!(
+            sel,
+            Selector {
+                module: Some(String::from("cpu")),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_selectors() {
+        assert!(matches!(
+            "cpu:zero:sys".parse::<Selector>(),
+            Err(Error::InvalidSelector(_))
+        ));
+        assert!(matches!(
+            "cpu:0:sys:user:extra".parse::<Selector>(),
+            Err(Error::InvalidSelector(_))
+        ));
+    }
+}
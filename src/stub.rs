@@ -0,0 +1,443 @@
+//! A stub backend for `libkstat`, for systems without kstats.
+//!
+//! With the `stubs` feature, this replaces `libkstat`. By default it presents an empty kstat
+//! chain, so that a [`Ctl`](crate::Ctl) may be created everywhere but there are simply no
+//! kstats. The chain may instead be seeded with [`FakeKstat`]s using [`set_chain`], to exercise
+//! code which iterates over, looks up, and reads kstats against a controlled dataset.
+//!
+//! Unlike the other features, `stubs` is not additive: it hides the real kstats from every user
+//! of this crate in the build. It must only be enabled by a binary or for tests, never by a
+//! library.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::sys;
+use crate::sys::hrtime_t;
+use crate::sys::kid_t;
+use crate::sys::kstat_ctl_t;
+use crate::sys::kstat_t;
+use crate::FakeKstat;
+use crate::StringField;
+use libc::c_char;
+use libc::c_int;
+use libc::c_void;
+use std::ffi::CStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+
+// A kstat chain, shared by every handle opened from it.
+struct Chain {
+    id: kid_t,
+    kstats: Vec<FakeKstat>,
+}
+
+// Safety: the raw pointers in a `FakeKstat` refer only to buffers owned by it, which are not
+// moved once the chain is built, so the chain may be shared between threads. As in the kernel,
+// the data of a writable kstat is overwritten in place by `kstat_write`.
+unsafe impl Send for Chain {}
+unsafe impl Sync for Chain {}
+
+// The chain most recently set, or `None` for the initial empty chain. It is shared by every
+// thread, so that a handle may be opened or used on any of them.
+static CHAIN: Mutex<Option<Arc<Chain>>> = Mutex::new(None);
+
+// Held by a `StubChain`, so that only one test at a time controls the chain.
+static OWNER: Mutex<()> = Mutex::new(());
+
+// A handle returned by the stub `kstat_open`. The `kstat_ctl_t` is first, so a pointer to it
+// is also a pointer to this.
+#[repr(C)]
+struct StubCtl {
+    ctl: kstat_ctl_t,
+    chain: Arc<Chain>,
+}
+
+impl StubCtl {
+    fn attach(&mut self, chain: Arc<Chain>) {
+        self.ctl.kc_chain_id = chain.id;
+        self.ctl.kc_chain = chain
+            .kstats
+            .first()
+            .map_or(std::ptr::null_mut(), |fake| &fake.ks as *const _ as *mut _);
+        self.chain = chain;
+    }
+
+    fn contains(&self, ks: *const kstat_t) -> bool {
+        self.chain
+            .kstats
+            .iter()
+            .any(|fake| std::ptr::eq(&fake.ks, ks))
+    }
+}
+
+/// Control of the kstat chain presented by the stub backend, returned by [`set_chain`].
+///
+/// The chain is shared by every thread in the process, so only one `StubChain` exists at a time,
+/// and tests which set the chain run one after another. When this is dropped the chain is
+/// emptied again, and the next call to [`set_chain`] may proceed.
+#[must_use = "the chain is emptied when this is dropped"]
+pub struct StubChain {
+    _owner: MutexGuard<'static, ()>,
+}
+
+impl StubChain {
+    /// Replace the chain with `kstats`.
+    ///
+    /// Existing handles see the new chain after they are updated, as they would a change to the
+    /// kernel's chain.
+    pub fn set(&self, kstats: Vec<FakeKstat>) {
+        replace_chain(kstats);
+    }
+}
+
+impl Drop for StubChain {
+    fn drop(&mut self) {
+        replace_chain(Vec::new());
+    }
+}
+
+/// Take control of the kstat chain presented by the stub backend, and set it to `kstats`.
+///
+/// This blocks until any other [`StubChain`] is dropped. A [`Ctl`](crate::Ctl) created
+/// afterwards, on any thread, sees `kstats` in order, as does an existing one after it is updated
+/// with [`Ctl::update_mut`](crate::Ctl::update_mut). Reading one of the kstats returns the data it
+/// was built with, or fails with `ENXIO` if no kstat with its kstat ID is in the current chain.
+/// Writing a writable kstat replaces its data.
+pub fn set_chain(kstats: Vec<FakeKstat>) -> StubChain {
+    let owner = OWNER.lock().unwrap_or_else(PoisonError::into_inner);
+    replace_chain(kstats);
+    StubChain { _owner: owner }
+}
+
+fn replace_chain(mut kstats: Vec<FakeKstat>) {
+    for i in 1..kstats.len() {
+        let next = &mut kstats[i].ks as *mut _;
+        kstats[i - 1].ks.ks_next = next;
+    }
+    if let Some(last) = kstats.last_mut() {
+        last.ks.ks_next = std::ptr::null_mut();
+    }
+    let mut chain = CHAIN.lock().unwrap_or_else(PoisonError::into_inner);
+    let id = chain.as_ref().map_or(0, |chain| chain.id) + 1;
+    *chain = Some(Arc::new(Chain { id, kstats }));
+}
+
+fn current_chain() -> Arc<Chain> {
+    let mut chain = CHAIN.lock().unwrap_or_else(PoisonError::into_inner);
+    Arc::clone(chain.get_or_insert_with(|| {
+        Arc::new(Chain {
+            id: 0,
+            kstats: Vec::new(),
+        })
+    }))
+}
+
+pub(crate) unsafe fn kstat_open() -> *mut kstat_ctl_t {
+    let chain = current_chain();
+    let mut ctl = Box::new(StubCtl {
+        ctl: kstat_ctl_t {
+            kc_chain_id: 0,
+            kc_chain: std::ptr::null_mut(),
+            kc_kd: -1,
+        },
+        chain: Arc::clone(&chain),
+    });
+    ctl.attach(chain);
+    Box::into_raw(ctl) as *mut kstat_ctl_t
+}
+
+pub(crate) unsafe fn kstat_close(ctl: *mut kstat_ctl_t) -> i32 {
+    if !ctl.is_null() {
+        drop(Box::from_raw(ctl as *mut StubCtl));
+    }
+    0
+}
+
+pub(crate) unsafe fn kstat_read(ctl: *mut kstat_ctl_t, ks: *mut kstat_t, _: *mut c_void) -> kid_t {
+    let ctl = &*(ctl as *const StubCtl);
+    if ks.is_null() || !ctl.contains(ks) {
+        set_errno(libc::ENOSYS);
+        return -1;
+    }
+    if is_gone(ctl, ks) {
+        set_errno(libc::ENXIO);
+        return -1;
+    }
+    ctl.ctl.kc_chain_id
+}
+
+// As in the kernel, a kstat which has been removed since the chain was last updated is gone,
+// even if another with the same name has replaced it.
+unsafe fn is_gone(ctl: &StubCtl, ks: *const kstat_t) -> bool {
+    let kid = (*ks).ks_kid;
+    let chain = current_chain();
+    !Arc::ptr_eq(&ctl.chain, &chain) && !chain.kstats.iter().any(|fake| fake.ks.ks_kid == kid)
+}
+
+pub(crate) unsafe fn kstat_chain_update(ctl: *mut kstat_ctl_t) -> kid_t {
+    let ctl = &mut *(ctl as *mut StubCtl);
+    let chain = current_chain();
+    if Arc::ptr_eq(&ctl.chain, &chain) {
+        0
+    } else {
+        ctl.attach(chain);
+        ctl.ctl.kc_chain_id
+    }
+}
+
+pub(crate) unsafe fn kstat_lookup(
+    ctl: *mut kstat_ctl_t,
+    module: *const c_char,
+    instance: c_int,
+    name: *const c_char,
+) -> *mut kstat_t {
+    let ctl = &*(ctl as *const StubCtl);
+    let matches = |want: *const c_char, field: &[c_char; sys::KSTAT_STRLEN], which| {
+        want.is_null()
+            || sys::array_to_bytes(field, which).ok() == Some(CStr::from_ptr(want).to_bytes())
+    };
+    let found = ctl.chain.kstats.iter().find(|fake| {
+        matches(module, &fake.ks.ks_module, StringField::Module)
+            && (instance == -1 || instance == fake.ks.ks_instance)
+            && matches(name, &fake.ks.ks_name, StringField::Name)
+    });
+    match found {
+        Some(fake) => &fake.ks as *const _ as *mut _,
+        None => {
+            set_errno(libc::ENOENT);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(feature = "write")]
+pub(crate) unsafe fn kstat_write(
+    ctl: *mut kstat_ctl_t,
+    ks: *mut kstat_t,
+    buf: *mut c_void,
+) -> kid_t {
+    let ctl = &*(ctl as *const StubCtl);
+    if ks.is_null() || !ctl.contains(ks) {
+        set_errno(libc::ENOSYS);
+        return -1;
+    }
+    if is_gone(ctl, ks) {
+        set_errno(libc::ENXIO);
+        return -1;
+    }
+    if (*ks).ks_flags as u8 & sys::KSTAT_FLAG_WRITABLE == 0 {
+        set_errno(libc::EACCES);
+        return -1;
+    }
+    std::ptr::copy_nonoverlapping(
+        buf as *const u8,
+        (*ks).ks_data as *mut u8,
+        (*ks).ks_data_size,
+    );
+    ctl.ctl.kc_chain_id
+}
+
+pub(crate) unsafe fn gethrtime() -> hrtime_t {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    ts.tv_sec as hrtime_t * 1_000_000_000 + ts.tv_nsec as hrtime_t
+}
+
+fn set_errno(errno: c_int) {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let location = unsafe { libc::__errno_location() };
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    let location = unsafe { libc::__error() };
+    #[cfg(any(target_os = "illumos", target_os = "solaris"))]
+    let location = unsafe { libc::___errno() };
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "illumos",
+        target_os = "solaris",
+    )))]
+    compile_error!("the stub backend cannot set errno on this target");
+    unsafe { *location = errno };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Ctl;
+    use crate::Data;
+    use crate::Error;
+    use crate::Instance;
+    use crate::KstatId;
+    use crate::NamedData;
+    use crate::OwnedData;
+    use crate::OwnedNamedData;
+    use crate::Type;
+
+    fn two_module_chain() -> Vec<FakeKstat> {
+        vec![
+            FakeKstat::new("cpu_info", 0, "cpu_info0", Type::Named)
+                .class("misc")
+                .named("clock_MHz", NamedData::Int64(2000)),
+            FakeKstat::new("cpu_info", 1, "cpu_info1", Type::Named)
+                .class("misc")
+                .named("clock_MHz", NamedData::Int64(3000)),
+            FakeKstat::new("zfs", 0, "arcstats", Type::Named)
+                .class("misc")
+                .named("size", NamedData::UInt64(1024)),
+        ]
+    }
+
+    #[test]
+    fn stub_chain_is_empty() {
+        let _chain = set_chain(Vec::new());
+        let mut ctl = Ctl::new().expect("Failed to create stub kstat control");
+        assert_eq!(ctl.iter().count(), 0);
+        assert_eq!(ctl.iter_raw().count(), 0);
+        assert!(!ctl.update_mut().expect("Failed to update stub chain"));
+        assert_eq!(ctl.iter().count(), 0);
+    }
+
+    #[test]
+    fn chain_is_shared_between_threads() {
+        let _chain = set_chain(two_module_chain());
+        let size = std::thread::spawn(|| {
+            let ctl = Ctl::new().expect("Failed to create stub kstat control");
+            let mut kstat = ctl
+                .lookup(Some("zfs"), Instance::Any, Some("arcstats"))
+                .expect("Failed to look up stub kstat")
+                .expect("Expected to find the arcstats kstat");
+            ctl.read(&mut kstat)
+                .expect("Failed to read stub kstat")
+                .to_owned()
+        })
+        .join()
+        .unwrap();
+        let OwnedData::Named(named) = size else {
+            panic!("expected named data");
+        };
+        assert_eq!(named[0].value, OwnedNamedData::UInt64(1024));
+
+        // A handle opened on one thread may be read on another.
+        let ctl = Ctl::new().expect("Failed to create stub kstat control");
+        let count = std::thread::spawn(move || {
+            let mut kstat = ctl
+                .lookup(Some("cpu_info"), Instance::Num(1), None)
+                .expect("Failed to look up stub kstat")
+                .expect("Expected to find a cpu_info kstat");
+            ctl.read(&mut kstat).map(|data| data.len())
+        })
+        .join()
+        .unwrap();
+        assert_eq!(count.expect("Failed to read stub kstat"), 1);
+    }
+
+    #[test]
+    fn stub_lookup_finds_nothing() {
+        let _chain = set_chain(Vec::new());
+        let ctl = Ctl::new().expect("Failed to create stub kstat control");
+        assert!(ctl
+            .lookup(Some("cpu_info"), Instance::Num(0), Some("cpu_info0"))
+            .expect("Failed to look up stub kstat")
+            .is_none());
+    }
+
+    #[test]
+    fn stub_read_fails() {
+        let _chain = set_chain(Vec::new());
+        let ctl = Ctl::new().expect("Failed to create stub kstat control");
+        let mut ks: sys::kstat_t = unsafe { std::mem::zeroed() };
+        ks.ks_type = sys::KSTAT_TYPE_NAMED;
+        let mut kstat = crate::Kstat::try_from(&ks).unwrap();
+        match ctl.read(&mut kstat) {
+            Err(Error::Io(e)) => assert_eq!(e.raw_os_error(), Some(libc::ENOSYS)),
+            other => panic!("expected ENOSYS, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn seeded_chain_filter_narrows() {
+        let _chain = set_chain(two_module_chain());
+        let ctl = Ctl::new().expect("Failed to create stub kstat control");
+        assert_eq!(ctl.iter().count(), 3);
+        let cpus = ctl
+            .filter(Some("cpu_info"), Instance::Any, None)
+            .collect::<Vec<_>>();
+        assert_eq!(cpus.len(), 2);
+        assert!(cpus.iter().all(|k| k.ks_module == "cpu_info"));
+        let cpu1 = ctl
+            .filter(Some("cpu_info"), Instance::Num(1), None)
+            .collect::<Vec<_>>();
+        assert_eq!(cpu1.len(), 1);
+        assert_eq!(cpu1[0].ks_name, "cpu_info1");
+        assert_eq!(ctl.filter(Some("zfs"), Instance::Any, None).count(), 1);
+        assert_eq!(ctl.filter(Some("unix"), Instance::Any, None).count(), 0);
+    }
+
+    #[test]
+    fn seeded_chain_lookup_and_read() {
+        let _chain = set_chain(two_module_chain());
+        let ctl = Ctl::new().expect("Failed to create stub kstat control");
+        let mut kstat = ctl
+            .lookup(Some("zfs"), Instance::Any, Some("arcstats"))
+            .expect("Failed to look up stub kstat")
+            .expect("Expected to find the arcstats kstat");
+        assert!(ctl
+            .lookup(Some("zfs"), Instance::Num(1), None)
+            .expect("Failed to look up stub kstat")
+            .is_none());
+        let Data::Named(named) = ctl.read(&mut kstat).expect("Failed to read stub kstat") else {
+            panic!("expected named data");
+        };
+        assert_eq!(named[0].name, "size");
+        assert_eq!(named[0].value, NamedData::UInt64(1024));
+    }
+
+    #[test]
+    fn seeded_chain_is_seen_after_update() {
+        let chain = set_chain(Vec::new());
+        let mut ctl = Ctl::new().expect("Failed to create stub kstat control");
+        let id = ctl.chain_id();
+        chain.set(two_module_chain());
+        assert_eq!(ctl.iter().count(), 0);
+        assert!(ctl.update_mut().expect("Failed to update stub chain"));
+        assert_ne!(ctl.chain_id(), id);
+        assert_eq!(ctl.iter().count(), 3);
+        assert!(!ctl.update_mut().expect("Failed to update stub chain"));
+    }
+
+    #[test]
+    fn update_diff_reports_added_and_removed() {
+        let disk = |instance| FakeKstat::new("sd", instance, &format!("sd{}", instance), Type::Io);
+        let chain = set_chain(vec![disk(0)]);
+        let mut ctl = Ctl::new().expect("Failed to create stub kstat control");
+        assert!(ctl.update_diff().expect("Failed to update").is_empty());
+        chain.set(vec![disk(0), disk(1)]);
+        let diff = ctl.update_diff().expect("Failed to update");
+        assert_eq!(diff.added, [KstatId::new("sd", 1, "sd1")]);
+        assert!(diff.removed.is_empty());
+        chain.set(vec![disk(1)]);
+        let diff = ctl.update_diff().expect("Failed to update");
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.removed, [KstatId::new("sd", 0, "sd0")]);
+    }
+}
@@ -0,0 +1,105 @@
+//! Sharing a kstat control handle between threads.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Error;
+use crate::KstatId;
+use crate::OwnedData;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+/// A [`Ctl`] which may be shared between threads.
+///
+/// A `Ctl` is `Send` but not `Sync`, since reading or updating kstats through it modifies the
+/// underlying `libkstat` handle. A `SyncCtl` guards the `Ctl` with a mutex, so all access
+/// through it is serialized. Since [`Kstat`](crate::Kstat)s borrow from the control handle,
+/// the methods here return owned data.
+#[derive(Debug)]
+pub struct SyncCtl {
+    ctl: Mutex<Ctl>,
+}
+
+impl SyncCtl {
+    /// Create a `SyncCtl` guarding `ctl`.
+    pub fn new(ctl: Ctl) -> Self {
+        SyncCtl {
+            ctl: Mutex::new(ctl),
+        }
+    }
+
+    /// Run `f` with exclusive access to the underlying `Ctl`.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Ctl) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Synchronize the underlying `Ctl` with the kernel, as with [`Ctl::update_mut`].
+    pub fn update(&self) -> Result<bool, Error> {
+        self.lock().update_mut()
+    }
+
+    /// Return the IDs of all the kstats in the chain.
+    pub fn iter(&self) -> impl Iterator<Item = KstatId> {
+        let ids = self.lock().iter().map(|k| k.id()).collect::<Vec<_>>();
+        ids.into_iter()
+    }
+
+    /// Read the kstat with the given module, instance, and name, as with [`Ctl::read_by_name`].
+    pub fn read(&self, module: &str, instance: i32, name: &str) -> Result<OwnedData, Error> {
+        self.lock()
+            .read_by_name(module, instance, name)
+            .map(|data| data.to_owned())
+    }
+
+    /// Consume the `SyncCtl`, returning the underlying `Ctl`.
+    pub fn into_inner(self) -> Ctl {
+        self.ctl.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // A panic while holding the lock does not leave the `Ctl` in an invalid state, so a
+    // poisoned lock is recovered.
+    fn lock(&self) -> MutexGuard<'_, Ctl> {
+        self.ctl.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// These read the real kstat chain, which the stub backend hides.
+#[cfg(all(test, not(feature = "stubs")))]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn read_from_two_threads() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let ctl = Arc::new(SyncCtl::new(ctl));
+        let threads = (0..2)
+            .map(|_| {
+                let ctl = Arc::clone(&ctl);
+                std::thread::spawn(move || {
+                    ctl.update().expect("Failed to update kstat chain");
+                    ctl.read("cpu_info", 0, "cpu_info0")
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            let data = thread.join().unwrap().expect("Failed to read kstat");
+            assert!(matches!(data, OwnedData::Named(_)));
+        }
+        assert!(ctl
+            .iter()
+            .any(|id| id == KstatId::new("cpu_info", 0, "cpu_info0")));
+    }
+}
@@ -17,13 +17,17 @@
 #![allow(non_camel_case_types)]
 
 use crate::Error;
-use libc::{c_char, c_int, c_longlong, c_uchar, c_uint, c_ulonglong, c_void, size_t};
-use std::convert::TryFrom;
-use std::ffi::CStr;
-use std::fmt::{self, Debug};
-use std::mem::size_of;
+use crate::StringField;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::ffi::CStr;
+use core::ffi::{c_char, c_int, c_long, c_longlong, c_uchar, c_uint, c_ulong, c_ulonglong, c_void};
+use core::fmt::{self, Debug};
+use core::mem::size_of;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
 
-// Rust FFI equivalent to `libkstat`'s `kstat_ctl_t`.
+/// Rust FFI equivalent to `libkstat`'s `kstat_ctl_t`.
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
 pub struct kstat_ctl_t {
@@ -41,6 +45,9 @@ pub type kid_t = c_int;
 // Length of string array fields
 pub const KSTAT_STRLEN: usize = 31;
 
+// Length of the character array of named data
+pub const KSTAT_NAMED_CHAR_LEN: usize = 16;
+
 // Kstat types
 pub const KSTAT_TYPE_RAW: u8 = 0;
 pub const KSTAT_TYPE_NAMED: u8 = 1;
@@ -48,6 +55,15 @@ pub const KSTAT_TYPE_INTR: u8 = 2;
 pub const KSTAT_TYPE_IO: u8 = 3;
 pub const KSTAT_TYPE_TIMER: u8 = 4;
 
+// Kstat flags
+pub const KSTAT_FLAG_VIRTUAL: u8 = 0x01;
+pub const KSTAT_FLAG_VAR_SIZE: u8 = 0x02;
+pub const KSTAT_FLAG_WRITABLE: u8 = 0x04;
+pub const KSTAT_FLAG_PERSISTENT: u8 = 0x08;
+pub const KSTAT_FLAG_DORMANT: u8 = 0x10;
+pub const KSTAT_FLAG_INVALID: u8 = 0x20;
+pub const KSTAT_FLAG_LONGSTRINGS: u8 = 0x40;
+
 // Rust FFI equivalent to `libkstat`'s `kstat_t`.
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
@@ -64,7 +80,7 @@ pub struct kstat_t {
     pub ks_flags: c_char,
     pub ks_data: *mut c_void,
     pub ks_ndata: c_uint,
-    pub ks_data_size: size_t,
+    pub ks_data_size: usize,
     pub ks_snaptime: hrtime_t,
 }
 
@@ -74,6 +90,10 @@ pub const KSTAT_DATA_INT32: u8 = 1;
 pub const KSTAT_DATA_UINT32: u8 = 2;
 pub const KSTAT_DATA_INT64: u8 = 3;
 pub const KSTAT_DATA_UINT64: u8 = 4;
+pub const KSTAT_DATA_FLOAT: u8 = 5;
+pub const KSTAT_DATA_DOUBLE: u8 = 6;
+pub const KSTAT_DATA_LONG: u8 = 7;
+pub const KSTAT_DATA_ULONG: u8 = 8;
 pub const KSTAT_DATA_STRING: u8 = 9;
 
 #[derive(Debug, Copy, Clone)]
@@ -87,12 +107,16 @@ pub struct kstat_named_t {
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub union NamedDataUnion {
-    pub charc: [c_uchar; 16],
+    pub charc: [c_uchar; KSTAT_NAMED_CHAR_LEN],
     pub str: NamedStr,
     pub i32: i32,
     pub ui32: u32,
     pub i64: i64,
     pub ui64: u64,
+    pub f: f32,
+    pub d: f64,
+    pub l: c_long,
+    pub ul: c_ulong,
 }
 
 impl Debug for NamedDataUnion {
@@ -111,12 +135,17 @@ pub struct NamedStr {
 impl TryFrom<&NamedStr> for &str {
     type Error = Error;
     fn try_from(n: &NamedStr) -> Result<Self, Self::Error> {
-        if n.addr.is_null() {
+        // The length is authoritative, and includes the terminating NUL if there is one.
+        if n.len == 0 {
+            Ok("")
+        } else if n.addr.is_null() {
             Err(Error::NullData)
         } else {
-            unsafe { CStr::from_ptr(n.addr) }
-                .to_str()
-                .map_err(|_| Error::InvalidString)
+            let bytes = unsafe { core::slice::from_raw_parts(n.addr as *const u8, n.len as _) };
+            let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            core::str::from_utf8(&bytes[..len]).map_err(|_| Error::InvalidString {
+                field: StringField::StatValue,
+            })
         }
     }
 }
@@ -162,66 +191,227 @@ pub struct kstat_io_t {
 }
 
 // Read a list of raw kstat data items from the given kstat.
-pub fn kstat_data_raw(kstat: &kstat_t) -> Vec<&[u8]> {
+pub fn kstat_data_raw(kstat: &kstat_t) -> Result<Vec<&[u8]>, Error> {
+    let mut out = Vec::new();
+    kstat_data_raw_into(kstat, &mut out)?;
+    Ok(out)
+}
+
+// Read a list of raw kstat data items from the given kstat into an existing vector, replacing
+// its contents.
+//
+// The buffer is split into `ks_ndata` items of equal size, so a buffer which doesn't divide evenly
+// is reported, as a partial trailing item is for the other types.
+pub fn kstat_data_raw_into<'a>(kstat: &'a kstat_t, out: &mut Vec<&'a [u8]>) -> Result<(), Error> {
+    out.clear();
     let n_data: usize = kstat.ks_ndata as _;
-    if n_data == 0 {
-        Vec::new()
-    } else {
-        let item_size = kstat.ks_data_size / n_data;
+    if let Some(item_size) = kstat.ks_data_size.checked_div(n_data) {
+        if kstat.ks_data_size % n_data != 0 {
+            return Err(Error::MisalignedData {
+                size: kstat.ks_data_size,
+                element_size: item_size,
+            });
+        }
         let mut start = kstat.ks_data as *const u8;
-        let mut out = Vec::with_capacity(n_data);
+        out.reserve(n_data);
         for _ in 0..kstat.ks_ndata {
-            out.push(unsafe { std::slice::from_raw_parts(start, item_size) });
+            out.push(unsafe { core::slice::from_raw_parts(start, item_size) });
             start = unsafe { start.add(item_size) };
         }
-        out
     }
+    Ok(())
+}
+
+// Read the entire data buffer of the given kstat.
+#[cfg(feature = "std")]
+pub fn kstat_data_bytes(kstat: &kstat_t) -> &[u8] {
+    if kstat.ks_data.is_null() || kstat.ks_data_size == 0 {
+        &[]
+    } else {
+        unsafe { core::slice::from_raw_parts(kstat.ks_data as *const u8, kstat.ks_data_size) }
+    }
+}
+
+// Check that `kstat` holds exactly one item of type `T`.
+fn check_single<T>(kstat: &kstat_t) -> Result<(), Error> {
+    if kstat.ks_ndata != 1 {
+        return Err(Error::UnexpectedDataShape {
+            expected: 1,
+            found: kstat.ks_ndata as _,
+        });
+    }
+    if kstat.ks_data_size != size_of::<T>() {
+        return Err(Error::SizeMismatch {
+            expected: size_of::<T>(),
+            found: kstat.ks_data_size,
+        });
+    }
+    Ok(())
+}
+
+// Check that the data buffer of `kstat` holds a whole number of items of type `T`.
+//
+// A partial trailing item indicates that the buffer is corrupt, or that its layout differs from
+// the one this crate was built for, so it is reported rather than ignored. This does not apply
+// to named kstats, which store the values of their strings after the array of items.
+fn check_whole<T>(kstat: &kstat_t) -> Result<(), Error> {
+    if kstat.ks_data_size % size_of::<T>() != 0 {
+        return Err(Error::MisalignedData {
+            size: kstat.ks_data_size,
+            element_size: size_of::<T>(),
+        });
+    }
+    Ok(())
+}
+
+// Check that the data buffer of `kstat` holds its `ks_ndata` name-value pairs, returning the
+// number of pairs.
+//
+// The buffer may be larger than the array of pairs, since the values of any strings are stored
+// after it.
+fn check_named(kstat: &kstat_t) -> Result<usize, Error> {
+    let count = kstat.ks_ndata as usize;
+    let expected = count * size_of::<kstat_named_t>();
+    if expected > kstat.ks_data_size {
+        return Err(Error::SizeMismatch {
+            expected,
+            found: kstat.ks_data_size,
+        });
+    }
+    Ok(count)
 }
 
 // Read an IO kstat from the given kstat.
-pub fn kstat_data_io(kstat: &kstat_t) -> &kstat_io_t {
-    assert!(kstat.ks_ndata == 1);
-    assert!(kstat.ks_data_size == size_of::<kstat_io_t>());
-    unsafe { (kstat.ks_data as *const kstat_io_t).as_ref() }.unwrap()
+pub fn kstat_data_io(kstat: &kstat_t) -> Result<&kstat_io_t, Error> {
+    check_single::<kstat_io_t>(kstat)?;
+    unsafe { (kstat.ks_data as *const kstat_io_t).as_ref() }.ok_or(Error::NullData)
 }
 
 // Read an interrupt kstat from the given kstat.
-pub fn kstat_data_intr(kstat: &kstat_t) -> &kstat_intr_t {
-    assert!(kstat.ks_ndata == 1);
-    assert!(kstat.ks_data_size == size_of::<kstat_intr_t>());
-    unsafe { (kstat.ks_data as *const kstat_intr_t).as_ref() }.unwrap()
+pub fn kstat_data_intr(kstat: &kstat_t) -> Result<&kstat_intr_t, Error> {
+    check_single::<kstat_intr_t>(kstat)?;
+    unsafe { (kstat.ks_data as *const kstat_intr_t).as_ref() }.ok_or(Error::NullData)
 }
 
 // Read a list of timer kstats from the given kstat.
-pub fn kstat_data_timer(kstat: &kstat_t) -> &[kstat_timer_t] {
-    assert!(kstat.ks_data_size == (kstat.ks_ndata as usize * size_of::<kstat_timer_t>()));
-    unsafe { std::slice::from_raw_parts(kstat.ks_ndata as *const _, kstat.ks_ndata as _) }
+pub fn kstat_data_timer(kstat: &kstat_t) -> Result<&[kstat_timer_t], Error> {
+    check_whole::<kstat_timer_t>(kstat)?;
+    let expected = kstat.ks_ndata as usize * size_of::<kstat_timer_t>();
+    if kstat.ks_data_size != expected {
+        return Err(Error::SizeMismatch {
+            expected,
+            found: kstat.ks_data_size,
+        });
+    }
+    if kstat.ks_data.is_null() || kstat.ks_ndata == 0 {
+        return Ok(&[]);
+    }
+    Ok(unsafe { core::slice::from_raw_parts(kstat.ks_data as *const _, kstat.ks_ndata as _) })
 }
 
 // Read a list of name-value kstats from the given kstat
-pub fn kstat_data_named(kstat: &kstat_t) -> &[kstat_named_t] {
-    let reported_count = kstat.ks_ndata as usize;
-    let actual_count = kstat.ks_data_size / size_of::<kstat_named_t>();
-    let count = reported_count.min(actual_count);
-    unsafe { std::slice::from_raw_parts(kstat.ks_data as *const _, count) }
+pub fn kstat_data_named(kstat: &kstat_t) -> Result<&[kstat_named_t], Error> {
+    let count = check_named(kstat)?;
+    if kstat.ks_data.is_null() || count == 0 {
+        return Ok(&[]);
+    }
+    Ok(unsafe { core::slice::from_raw_parts(kstat.ks_data as *const _, count) })
 }
 
+// Read a mutable list of name-value kstats from the given kstat.
+pub fn kstat_data_named_mut(kstat: &mut kstat_t) -> Result<&mut [kstat_named_t], Error> {
+    let count = check_named(kstat)?;
+    if kstat.ks_data.is_null() || count == 0 {
+        return Ok(&mut []);
+    }
+    Ok(unsafe { core::slice::from_raw_parts_mut(kstat.ks_data as *mut _, count) })
+}
+
+// Copy the data buffer of the given kstat into a new buffer, suitably aligned for any of the
+// kstat data types.
+#[cfg(feature = "write")]
+pub fn kstat_data_copy(kstat: &kstat_t) -> Vec<u64> {
+    let mut buf = vec![0u64; kstat.ks_data_size.div_ceil(size_of::<u64>())];
+    if !kstat.ks_data.is_null() {
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                kstat.ks_data as *const u8,
+                buf.as_mut_ptr() as *mut u8,
+                kstat.ks_data_size,
+            )
+        };
+    }
+    buf
+}
+
+#[cfg(feature = "std")]
+#[cfg(not(feature = "stubs"))]
 #[link(name = "kstat")]
 extern "C" {
     pub fn kstat_open() -> *mut kstat_ctl_t;
     pub fn kstat_close(_: *mut kstat_ctl_t) -> i32;
     pub fn kstat_read(_: *mut kstat_ctl_t, _: *mut kstat_t, _: *mut c_void) -> kid_t;
     pub fn kstat_chain_update(_: *mut kstat_ctl_t) -> kid_t;
+    pub fn kstat_lookup(
+        _: *mut kstat_ctl_t,
+        _: *const c_char,
+        _: c_int,
+        _: *const c_char,
+    ) -> *mut kstat_t;
+    #[cfg(feature = "write")]
+    pub fn kstat_write(_: *mut kstat_ctl_t, _: *mut kstat_t, _: *mut c_void) -> kid_t;
+}
+
+#[cfg(feature = "std")]
+#[cfg(not(feature = "stubs"))]
+extern "C" {
+    pub fn gethrtime() -> hrtime_t;
+}
+
+#[cfg(feature = "stubs")]
+#[cfg(feature = "write")]
+pub(crate) use crate::stub::kstat_write;
+#[cfg(feature = "stubs")]
+pub(crate) use crate::stub::{
+    gethrtime, kstat_chain_update, kstat_close, kstat_lookup, kstat_open, kstat_read,
+};
+
+// Helper to view the whole of a Kstat string array as bytes.
+pub(crate) fn array_as_bytes(s: &[c_char; KSTAT_STRLEN]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(s.as_ptr() as *const u8, s.len()) }
+}
+
+// Helper to convert a Kstat string array to a C string, which must end within the array.
+fn array_to_c(s: &[c_char; KSTAT_STRLEN], field: StringField) -> Result<&CStr, Error> {
+    CStr::from_bytes_until_nul(array_as_bytes(s)).map_err(|_| Error::InvalidString { field })
 }
 
 // Helper to convert a Kstat string array to a &str.
-pub(crate) fn array_to_cstr(s: &[c_char; KSTAT_STRLEN]) -> Result<&str, Error> {
-    unsafe { CStr::from_ptr(s.as_ptr() as *const _) }
+pub(crate) fn array_to_cstr(s: &[c_char; KSTAT_STRLEN], field: StringField) -> Result<&str, Error> {
+    array_to_c(s, field)?
         .to_str()
-        .map_err(|_| Error::InvalidString)
+        .map_err(|_| Error::InvalidString { field })
+}
+
+// Helper to return the bytes of a Kstat string array, up to the first NUL.
+#[cfg(feature = "std")]
+pub(crate) fn array_to_bytes(
+    s: &[c_char; KSTAT_STRLEN],
+    field: StringField,
+) -> Result<&[u8], Error> {
+    array_to_c(s, field).map(CStr::to_bytes)
 }
 
-#[cfg(test)]
+// Helper to convert a Kstat string array to a string, replacing invalid UTF-8.
+#[cfg(feature = "std")]
+pub(crate) fn array_to_cstr_lossy(
+    s: &[c_char; KSTAT_STRLEN],
+    field: StringField,
+) -> Result<Cow<'_, str>, Error> {
+    array_to_c(s, field).map(CStr::to_string_lossy)
+}
+
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::*;
 
@@ -232,6 +422,45 @@ mod test {
         assert_eq!(unsafe { kstat_close(ctl) }, 0);
     }
 
+    #[test]
+    fn arrays_without_nul_are_invalid() {
+        let s = [b'a' as c_char; KSTAT_STRLEN];
+        assert!(matches!(
+            array_to_cstr(&s, StringField::Module),
+            Err(Error::InvalidString {
+                field: StringField::Module
+            })
+        ));
+        assert!(array_to_bytes(&s, StringField::Name).is_err());
+        assert!(array_to_cstr_lossy(&s, StringField::Class).is_err());
+        assert_eq!(array_as_bytes(&s), &[b'a'; KSTAT_STRLEN]);
+
+        let mut s = s;
+        s[3] = 0;
+        assert_eq!(array_to_cstr(&s, StringField::Module).unwrap(), "aaa");
+        assert_eq!(array_to_bytes(&s, StringField::Name).unwrap(), b"aaa");
+    }
+
+    #[test]
+    fn named_str_uses_length() {
+        let buf = b"hello world\0";
+        let s = NamedStr {
+            addr: buf.as_ptr() as *const _,
+            len: 5,
+        };
+        assert_eq!(<&str>::try_from(&s).unwrap(), "hello");
+        let s = NamedStr {
+            addr: buf.as_ptr() as *const _,
+            len: buf.len() as _,
+        };
+        assert_eq!(<&str>::try_from(&s).unwrap(), "hello world");
+        let s = NamedStr {
+            addr: std::ptr::null(),
+            len: 0,
+        };
+        assert_eq!(<&str>::try_from(&s).unwrap(), "");
+    }
+
     /*
     #[test]
     fn foo() {
@@ -245,7 +474,7 @@ mod test {
             } else {
                 let ty = Type::try_from(ks.ks_type).unwrap();
                 if matches!(ty, Type::Io) {
-                    let data = Io::from(kstat_data_io(ks));
+                    let data = Io::from(kstat_data_io(ks).unwrap());
                     println!("name: {}\n{:#?}", array_to_cstr(&ks.ks_name).unwrap(), data);
                 } else if matches!(ty, Type::Named) {
                     let data = kstat_data_named(ks).iter().map(|d| Named::try_from(d).unwrap()).collect::<Vec<_>>();
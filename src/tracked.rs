@@ -0,0 +1,118 @@
+//! Reading a single kstat repeatedly, across updates of the kstat chain.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Error;
+use crate::Instance;
+use crate::KstatId;
+use crate::OwnedData;
+
+/// A handle to a single kstat, which may be read repeatedly across updates of the kstat chain.
+///
+/// A [`Kstat`](crate::Kstat) borrows from the [`Ctl`] it was found through, and its pointer into
+/// the chain may be freed by [`Ctl::update_mut`]. A `Tracked` instead remembers the [`KstatId`]
+/// of the kstat, and looks it up again on each [`Tracked::poll`], so it may be kept across
+/// updates.
+#[derive(Clone, Debug)]
+pub struct Tracked {
+    id: KstatId,
+    kid: Option<i32>,
+}
+
+impl Tracked {
+    /// Create a handle tracking the kstat with the given ID.
+    pub fn new(id: KstatId) -> Self {
+        Tracked { id, kid: None }
+    }
+
+    /// Return the ID of the tracked kstat.
+    pub fn id(&self) -> &KstatId {
+        &self.id
+    }
+
+    /// Return the kstat ID (`ks_kid`) of the tracked kstat when it was last read.
+    ///
+    /// This is `None` until the kstat has been read. A kstat which is removed and created again,
+    /// such as when a device is detached and reattached, is given a new kstat ID.
+    pub fn kid(&self) -> Option<i32> {
+        self.kid
+    }
+
+    /// Look up the tracked kstat in the chain of `ctl`, and read it.
+    ///
+    /// [`Error::NotFound`] is returned if the kstat is not in the chain. The kstat is looked up
+    /// on every call, so this continues to work after the chain is updated, and finds the kstat
+    /// again if it is removed and later recreated.
+    pub fn poll(&mut self, ctl: &Ctl) -> Result<OwnedData, Error> {
+        let mut kstat = ctl
+            .lookup(
+                Some(&self.id.module),
+                Instance::Num(self.id.instance),
+                Some(&self.id.name),
+            )?
+            .ok_or(Error::NotFound)?;
+        let data = ctl.read(&mut kstat)?.to_owned();
+        self.kid = Some(kstat.kid());
+        Ok(data)
+    }
+}
+
+impl From<KstatId> for Tracked {
+    fn from(id: KstatId) -> Self {
+        Tracked::new(id)
+    }
+}
+
+#[cfg(all(test, feature = "stubs"))]
+mod test {
+    use super::*;
+    use crate::fake::arcstats;
+    use crate::FakeKstat;
+    use crate::OwnedNamedData;
+    use crate::Type;
+
+    #[test]
+    fn tracked_kstat_survives_update() {
+        let chain = crate::stub::set_chain(vec![arcstats(1, 1024)]);
+        let mut ctl = Ctl::new().expect("Failed to create stub kstat control");
+        let mut tracked = Tracked::new(KstatId::new("zfs", 0, "arcstats"));
+        let size = |data: OwnedData| match data {
+            OwnedData::Named(named) => named[0].value.clone(),
+            other => panic!("expected named data, found {:?}", other),
+        };
+        assert_eq!(
+            size(tracked.poll(&ctl).expect("Failed to poll")),
+            OwnedNamedData::UInt64(1024)
+        );
+        assert_eq!(tracked.kid(), Some(1));
+
+        // Recreate the kstat at a new position in the chain, as a reattached device would be.
+        chain.set(vec![
+            FakeKstat::new("zfs", 0, "vdev_cache_stats", Type::Named).kid(2),
+            arcstats(3, 2048),
+        ]);
+        assert!(ctl.update_mut().expect("Failed to update"));
+        assert_eq!(
+            size(tracked.poll(&ctl).expect("Failed to poll")),
+            OwnedNamedData::UInt64(2048)
+        );
+        assert_eq!(tracked.kid(), Some(3));
+
+        chain.set(Vec::new());
+        assert!(ctl.update_mut().expect("Failed to update"));
+        assert!(matches!(tracked.poll(&ctl), Err(Error::NotFound)));
+    }
+}
@@ -0,0 +1,135 @@
+//! Heuristics for inferring the units of named kstat values from their names.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::OnceLock;
+
+/// The unit of a named kstat value.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Unit {
+    /// A size or amount of data, in bytes.
+    Bytes,
+    /// A frequency, in hertz.
+    Hertz,
+    /// A frequency, in megahertz.
+    Megahertz,
+    /// A duration or time, in nanoseconds.
+    Nanoseconds,
+    /// A count of events or items.
+    Count,
+    /// A percentage.
+    Percent,
+}
+
+/// Rules for inferring the [`Unit`] of a named kstat value from its name.
+///
+/// Kstats do not record the units of their values, so this guesses from the suffix of the name
+/// of each statistic. The first rule whose suffix the name ends with is used, and the match is
+/// case-sensitive. The default rules are:
+///
+/// | Suffix                     | Unit          |
+/// |----------------------------|---------------|
+/// | `bytes`, `bytes64`         | `Bytes`       |
+/// | `_Hz`                      | `Hertz`       |
+/// | `_MHz`                     | `Megahertz`   |
+/// | `nsec`, `_ns`              | `Nanoseconds` |
+/// | `_pct`, `_percent`         | `Percent`     |
+/// | `packets`, `packets64`, `errors`, `_count`, `_total` | `Count` |
+///
+/// The same suffixes pick out counters for the default [`CounterHeuristic`]. The rules may be
+/// replaced or extended to suit a particular system.
+///
+/// [`CounterHeuristic`]: crate::CounterHeuristic
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnitRules {
+    /// Suffixes of statistic names, and the unit of statistics with that suffix.
+    pub suffixes: Vec<(String, Unit)>,
+}
+
+// The default suffixes of statistic names, and the units of statistics with them.
+pub(crate) const DEFAULT_SUFFIXES: &[(&str, Unit)] = &[
+    ("bytes", Unit::Bytes),
+    ("bytes64", Unit::Bytes),
+    ("_Hz", Unit::Hertz),
+    ("_MHz", Unit::Megahertz),
+    ("nsec", Unit::Nanoseconds),
+    ("_ns", Unit::Nanoseconds),
+    ("_pct", Unit::Percent),
+    ("_percent", Unit::Percent),
+    ("packets", Unit::Count),
+    ("packets64", Unit::Count),
+    ("errors", Unit::Count),
+    ("_count", Unit::Count),
+    ("_total", Unit::Count),
+];
+
+impl Default for UnitRules {
+    fn default() -> Self {
+        UnitRules {
+            suffixes: DEFAULT_SUFFIXES
+                .iter()
+                .map(|(s, unit)| (s.to_string(), *unit))
+                .collect(),
+        }
+    }
+}
+
+impl UnitRules {
+    // The default rules, built once.
+    pub(crate) fn default_ref() -> &'static UnitRules {
+        static DEFAULT: OnceLock<UnitRules> = OnceLock::new();
+        DEFAULT.get_or_init(UnitRules::default)
+    }
+
+    /// Return the unit of the statistic `name`, if any rule matches it.
+    pub fn infer(&self, name: &str) -> Option<Unit> {
+        self.suffixes
+            .iter()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(|(_, unit)| *unit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_rules() {
+        let rules = UnitRules::default();
+        assert_eq!(rules.infer("rbytes64"), Some(Unit::Bytes));
+        assert_eq!(rules.infer("obytes"), Some(Unit::Bytes));
+        assert_eq!(rules.infer("current_clock_Hz"), Some(Unit::Hertz));
+        assert_eq!(rules.infer("clock_MHz"), Some(Unit::Megahertz));
+        assert_eq!(rules.infer("cpu_nsec_user"), None);
+        assert_eq!(rules.infer("snaptime_nsec"), Some(Unit::Nanoseconds));
+        assert_eq!(rules.infer("ipackets64"), Some(Unit::Count));
+        assert_eq!(rules.infer("ierrors"), Some(Unit::Count));
+        assert_eq!(rules.infer("util_pct"), Some(Unit::Percent));
+        assert_eq!(rules.infer("state"), None);
+        assert_eq!(rules.infer("ipkts_total"), Some(Unit::Count));
+    }
+
+    #[test]
+    fn rules_are_extensible() {
+        let mut rules = UnitRules::default();
+        rules
+            .suffixes
+            .insert(0, (String::from("_refs"), Unit::Count));
+        assert_eq!(rules.infer("dnode_refs"), Some(Unit::Count));
+        rules.suffixes.clear();
+        assert_eq!(rules.infer("rbytes64"), None);
+    }
+}
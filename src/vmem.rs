@@ -0,0 +1,115 @@
+//! Helpers for extracting allocation arena statistics from the `vmem` kstats.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Data;
+use crate::Error;
+use crate::Kstat;
+
+/// The statistics of a single `vmem` allocation arena.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VmemArena {
+    /// The name of the arena, which is the name of its kstat.
+    pub name: String,
+    /// The instance of the kstat, which is the ID of the arena.
+    pub instance: i32,
+    /// The number of bytes allocated from the arena.
+    pub mem_inuse: u64,
+    /// The total number of bytes in the arena.
+    pub mem_total: u64,
+    /// The number of allocations from the arena.
+    pub alloc: u64,
+    /// The number of frees to the arena.
+    pub free: u64,
+    /// The number of failed allocations from the arena.
+    pub fail: u64,
+}
+
+/// Read the statistics of every arena from the kstats of class `vmem`, sorted by instance.
+///
+/// Arenas whose kstat lacks one of the fields of [`VmemArena`] are skipped, and logged if the
+/// `log` feature is enabled, as are kstats which are removed from the chain while being read.
+/// Any other failure to read a kstat is returned.
+pub fn vmem_stats(ctl: &Ctl) -> Result<Vec<VmemArena>, Error> {
+    let mut arenas = ctl.decode_each(ctl.filter_class("vmem"), arena)?;
+    arenas.sort_by_key(|arena| arena.instance);
+    Ok(arenas)
+}
+
+fn arena(kstat: &Kstat<'_>, data: &Data<'_>) -> Result<VmemArena, Error> {
+    Ok(VmemArena {
+        name: kstat.ks_name.to_string(),
+        instance: kstat.ks_instance,
+        mem_inuse: data.named_u64("mem_inuse")?,
+        mem_total: data.named_u64("mem_total")?,
+        alloc: data.named_u64("alloc")?,
+        free: data.named_u64("free")?,
+        fail: data.named_u64("fail")?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // This reads the real kstat chain, which the stub backend hides.
+    #[cfg(not(feature = "stubs"))]
+    #[test]
+    fn vmem_arenas_within_total() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let arenas = vmem_stats(&ctl).expect("Failed to read vmem stats");
+        assert!(!arenas.is_empty());
+        assert!(arenas
+            .iter()
+            .all(|arena| arena.mem_inuse <= arena.mem_total));
+    }
+
+    #[cfg(feature = "stubs")]
+    #[test]
+    fn vmem_stats_skips_incomplete_arenas() {
+        use crate::FakeKstat;
+        use crate::NamedData;
+        use crate::Type;
+
+        let arena = |instance, name: &str| {
+            FakeKstat::new("vmem", instance, name, Type::Named)
+                .class("vmem")
+                .named("mem_inuse", NamedData::UInt64(4096))
+                .named("mem_total", NamedData::UInt64(8192))
+                .named("alloc", NamedData::UInt64(3))
+                .named("free", NamedData::UInt64(2))
+                .named("fail", NamedData::UInt64(0))
+        };
+        let _chain = crate::stub::set_chain(vec![
+            arena(3, "kmem_va"),
+            FakeKstat::new("vmem", 2, "partial", Type::Named)
+                .class("vmem")
+                .named("mem_inuse", NamedData::UInt64(1)),
+            arena(1, "heap"),
+        ]);
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let arenas = vmem_stats(&ctl).expect("Failed to read vmem stats");
+        assert_eq!(
+            arenas
+                .iter()
+                .map(|arena| (arena.instance, arena.name.as_str()))
+                .collect::<Vec<_>>(),
+            [(1, "heap"), (3, "kmem_va")]
+        );
+        assert_eq!(arenas[0].mem_total, 8192);
+    }
+}
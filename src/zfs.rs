@@ -0,0 +1,77 @@
+//! Helpers for extracting ZFS ARC statistics from the `zfs:0:arcstats` kstat.
+
+// Copyright 2023 Oxide Computer Company
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::Ctl;
+use crate::Error;
+
+/// Commonly-used statistics of the ZFS Adaptive Replacement Cache (ARC).
+///
+/// Statistics which are missing from the kstat, for example on older kernels, are reported as
+/// zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ArcStats {
+    /// Number of ARC hits.
+    pub hits: u64,
+    /// Number of ARC misses.
+    pub misses: u64,
+    /// Current size of the ARC, in bytes.
+    pub size: u64,
+    /// Target size of the ARC, in bytes.
+    pub c: u64,
+    /// Maximum target size of the ARC, in bytes.
+    pub c_max: u64,
+    /// Target size of the most-recently-used portion of the ARC, in bytes.
+    pub p: u64,
+    /// Size of cached file data, in bytes.
+    pub data_size: u64,
+    /// Size of cached metadata, in bytes.
+    pub metadata_size: u64,
+}
+
+/// Read the statistics of the ZFS ARC.
+///
+/// This returns [`Error::NotFound`] if there is no `zfs:0:arcstats` kstat, for example if ZFS
+/// is not loaded.
+pub fn arc_stats(ctl: &Ctl) -> Result<ArcStats, Error> {
+    let data = ctl.read_by_name("zfs", 0, "arcstats")?;
+    let stat = |name| data.named_u64(name).unwrap_or(0);
+    Ok(ArcStats {
+        hits: stat("hits"),
+        misses: stat("misses"),
+        size: stat("size"),
+        c: stat("c"),
+        c_max: stat("c_max"),
+        p: stat("p"),
+        data_size: stat("data_size"),
+        metadata_size: stat("metadata_size"),
+    })
+}
+
+// These read the real kstat chain, which the stub backend hides.
+#[cfg(all(test, not(feature = "stubs")))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn arc_size_within_max() {
+        let ctl = Ctl::new().expect("Failed to create kstat control");
+        let stats = arc_stats(&ctl).expect("Failed to read ARC stats");
+        if stats.size != 0 && stats.c_max != 0 {
+            assert!(stats.size <= stats.c_max);
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use kstat_rs::Ctl;
+
+fn main() {
+    let mut ctl = Ctl::new().unwrap();
+    let mut iter = ctl.iter();
+    ctl.update_mut().unwrap();
+    iter.next();
+}
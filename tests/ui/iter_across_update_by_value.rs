@@ -0,0 +1,8 @@
+use kstat_rs::Ctl;
+
+fn main() {
+    let ctl = Ctl::new().unwrap();
+    let mut iter = ctl.iter();
+    let _ctl = ctl.update().unwrap();
+    iter.next();
+}
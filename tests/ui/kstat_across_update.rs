@@ -0,0 +1,8 @@
+use kstat_rs::Ctl;
+
+fn main() {
+    let mut ctl = Ctl::new().unwrap();
+    let kstat = ctl.iter().next().unwrap();
+    ctl.update_mut().unwrap();
+    println!("{}", kstat.ks_name);
+}